@@ -0,0 +1,160 @@
+//! Measures the emulation speed on a synthetic program mixing the loads,
+//! the stores, the ALU, the read-modify-write instructions, the branches
+//! and the subroutine calls in most of the addressing modes.
+
+use std::time::Instant;
+
+use clap::Parser;
+
+use yamos6502::encode_insn;
+use yamos6502::AddressMode;
+use yamos6502::BatchExit;
+use yamos6502::BatchSummary;
+use yamos6502::Budget;
+use yamos6502::Insn;
+use yamos6502::Memory;
+use yamos6502::MemoryError;
+use yamos6502::Mos6502;
+use yamos6502::RegisterFile;
+use yamos6502::StackWraparound;
+use yamos6502::MAX_MEMORY_SIZE;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Instructions to emulate.
+    #[arg(long, default_value_t = 100_000_000)]
+    instructions: u64,
+    /// Call `Mos6502::run` for every instruction instead of running a batch.
+    #[arg(long, default_value_t = false)]
+    run_loop: bool,
+}
+
+struct Ram {
+    cells: Vec<u8>,
+}
+
+impl Memory for Ram {
+    fn read(&mut self, addr: u16) -> Result<u8, MemoryError> {
+        Ok(self.cells[addr as usize])
+    }
+
+    fn write(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.cells[addr as usize] = value;
+        Ok(())
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.cells[addr as usize])
+    }
+}
+
+const START: u16 = 0x0400;
+const SUBROUTINE: u16 = 0x0500;
+
+fn program() -> Vec<(u16, Vec<u8>)> {
+    use AddressMode::*;
+
+    let main = vec![
+        // start:
+        encode_insn(Insn::LDX(Immediate)),
+        0x00,
+        // loop:
+        encode_insn(Insn::LDA(AbsoluteX)),
+        0x00,
+        0x10,
+        encode_insn(Insn::ADC(Immediate)),
+        0x03,
+        encode_insn(Insn::STA(AbsoluteX)),
+        0x00,
+        0x11,
+        encode_insn(Insn::EOR(IndirectY)),
+        0x20,
+        encode_insn(Insn::AND(Zeropage)),
+        0x30,
+        encode_insn(Insn::ORA(ZeropageX)),
+        0x31,
+        encode_insn(Insn::ASL(Zeropage)),
+        0x40,
+        encode_insn(Insn::ROR(ZeropageX)),
+        0x41,
+        encode_insn(Insn::INC(Absolute)),
+        0x00,
+        0x12,
+        encode_insn(Insn::DEC(Zeropage)),
+        0x50,
+        encode_insn(Insn::CMP(Immediate)),
+        0x80,
+        encode_insn(Insn::BCC(Relative)),
+        0x01,
+        encode_insn(Insn::CLC),
+        // skip:
+        encode_insn(Insn::JSR(Absolute)),
+        SUBROUTINE as u8,
+        (SUBROUTINE >> 8) as u8,
+        encode_insn(Insn::INX),
+        encode_insn(Insn::BNE(Relative)),
+        0xde,
+        encode_insn(Insn::INY),
+        encode_insn(Insn::JMP(Absolute)),
+        START as u8,
+        (START >> 8) as u8,
+    ];
+    let subroutine = vec![
+        encode_insn(Insn::PHA),
+        encode_insn(Insn::TXA),
+        encode_insn(Insn::SBC(Immediate)),
+        0x01,
+        encode_insn(Insn::PLA),
+        encode_insn(Insn::RTS),
+    ];
+
+    vec![(START, main), (SUBROUTINE, subroutine)]
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut cells = vec![0; MAX_MEMORY_SIZE];
+    for (addr, bytes) in program() {
+        cells[addr as usize..addr as usize + bytes.len()].copy_from_slice(&bytes);
+    }
+    // The pointer for (zp),Y
+    cells[0x20] = 0x00;
+    cells[0x21] = 0x13;
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(START);
+    *regf.sp_mut() = 0xff;
+    let mut mos6502 = Mos6502::with_registers(Ram { cells }, regf, StackWraparound::Allow);
+
+    let start = Instant::now();
+    let budget = Budget::Instructions(args.instructions);
+    let summary = if args.run_loop {
+        let cycles = mos6502.cycles();
+        for _ in 0..args.instructions {
+            mos6502.run().unwrap();
+        }
+        BatchSummary {
+            exit: BatchExit::BudgetExhausted,
+            instructions: args.instructions,
+            cycles: mos6502.cycles() - cycles,
+        }
+    } else {
+        mos6502.run_batch(budget, &[])
+    };
+    let elapsed = start.elapsed();
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    // Still in the program
+    let pc = mos6502.registers().pc();
+    assert!((START..START + 0x40).contains(&pc) || (SUBROUTINE..SUBROUTINE + 0x10).contains(&pc));
+
+    let seconds = elapsed.as_secs_f64();
+    println!(
+        "{} instructions, {} cycles in {seconds:.3} s: {:.1} MIPS, {:.1} MHz",
+        summary.instructions,
+        summary.cycles,
+        summary.instructions as f64 / seconds / 1e6,
+        summary.cycles as f64 / seconds / 1e6,
+    );
+}
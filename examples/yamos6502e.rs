@@ -1,12 +1,42 @@
 use clap::Parser;
 use clap_num::maybe_hex;
 
+use yamos6502::get_opcode_string_for;
+use yamos6502::BatchExit;
+use yamos6502::Budget;
+use yamos6502::CpuVariant;
 use yamos6502::Memory;
 use yamos6502::RunExit;
 use yamos6502::StackWraparound;
 use yamos6502::MAX_MEMORY_SIZE;
 use yamos6502::RESET_VECTOR;
 
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Cpu {
+    /// NMOS 6502
+    Nmos6502,
+    /// WDC 65C02
+    Wdc65c02,
+    /// Ricoh 2A03
+    Ricoh2a03,
+    /// MOS 6510
+    Mos6510,
+    /// MOS 6507
+    Mos6507,
+}
+
+impl From<Cpu> for CpuVariant {
+    fn from(cpu: Cpu) -> Self {
+        match cpu {
+            Cpu::Nmos6502 => CpuVariant::Nmos6502,
+            Cpu::Wdc65c02 => CpuVariant::Wdc65C02,
+            Cpu::Ricoh2a03 => CpuVariant::Ricoh2A03,
+            Cpu::Mos6510 => CpuVariant::Mos6510,
+            Cpu::Mos6507 => CpuVariant::Mos6507,
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -24,6 +54,9 @@ struct Args {
     /// Program counter at which exit
     #[arg(long, default_value_t = 0x3469, value_parser=maybe_hex::<u16>)]
     exit_pc: u16,
+    /// Processor to emulate.
+    #[arg(long, value_enum, default_value_t = Cpu::Nmos6502)]
+    cpu: Cpu,
     /// Allow stack wraparound.
     #[arg(long, default_value_t = false)]
     stack_wraparound: bool,
@@ -33,6 +66,10 @@ struct Args {
     /// Pause in milliseconds between executing instructions
     #[arg(long)]
     pause_millis: Option<u64>,
+    /// Run in batches of that many instructions, without logging every
+    /// instruction and pausing
+    #[arg(long)]
+    batch: Option<u64>,
     /// Dead loop iterations before exit
     #[clap(long, default_value_t = 0x10000, value_parser=maybe_hex::<u64>)]
     dead_loop_iterations: u64,
@@ -78,6 +115,10 @@ impl Memory for RomRam {
 
         Ok(value)
     }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.cells.get(addr as usize).copied()
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -139,16 +180,106 @@ fn main() -> anyhow::Result<()> {
     log::info!("Will exit at 0x{:04x?}", args.exit_pc);
 
     let memory = RomRam::new(memory, args.rom_start);
-    let mut mos6502 = yamos6502::Mos6502::new(memory, allow_stack_wraparound);
+    let variant = CpuVariant::from(args.cpu);
+    let mut mos6502 = yamos6502::Mos6502::with_variant(memory, variant, allow_stack_wraparound);
 
-    log::info!("Running MOS 6502 emulator");
+    log::info!("Running {variant:?} emulator");
 
     mos6502.set_reset_pending();
 
+    if let Some(batch) = args.batch {
+        let mut instructions_emulated = 0;
+        let mut dead_loop_iterations = 0;
+        loop {
+            // The batch ends where the statistics are due
+            let mut budget = batch;
+            if args.print_stats != 0 {
+                budget = budget.min(args.print_stats - instructions_emulated % args.print_stats);
+            }
+
+            // An instruction retiring at the PC it has started at is
+            // a dead loop iteration, so the batch stops back at its start.
+            // Having come back after more instructions, it goes on.
+            let start_pc = mos6502.registers().pc();
+            let summary =
+                mos6502.run_batch(Budget::Instructions(budget), &[args.exit_pc, start_pc]);
+            let (exit, instructions) = match summary.exit {
+                BatchExit::StopPc(pc) if pc != args.exit_pc && summary.instructions > 1 => {
+                    dead_loop_iterations = 0;
+                    let rest = mos6502.run_batch(
+                        Budget::Instructions(budget - summary.instructions),
+                        &[args.exit_pc],
+                    );
+                    (rest.exit, summary.instructions + rest.instructions)
+                }
+                BatchExit::StopPc(pc) if pc != args.exit_pc => {
+                    dead_loop_iterations += 1;
+                    (summary.exit, summary.instructions)
+                }
+                exit => {
+                    dead_loop_iterations = 0;
+                    (exit, summary.instructions)
+                }
+            };
+            instructions_emulated += instructions;
+
+            if args.print_stats != 0
+                && instructions != 0
+                && instructions_emulated % args.print_stats == 0
+            {
+                log::info!("Instructions emulated: {instructions_emulated}");
+                log::info!("Cycles elapsed: {}", mos6502.cycles());
+                log::info!("Last one at: {:04x?}", mos6502.registers());
+            }
+
+            match exit {
+                BatchExit::StopPc(pc) if pc == args.exit_pc => {
+                    log::info!("Exiting as the program is at the exit PC 0x{pc:04x}",);
+                    log::info!("Instructions emulated: {instructions_emulated}");
+                    log::info!("Cycles elapsed: {}", mos6502.cycles());
+                    log::info!("{:04x?}", mos6502.registers());
+                    break;
+                }
+                BatchExit::BudgetExhausted | BatchExit::StopPc(_) => {}
+                BatchExit::Event(RunExit::Stopped) => {
+                    log::info!("Processor stopped, {:04x?}", mos6502.registers());
+                    break;
+                }
+                BatchExit::Event(exit) => log::debug!("{exit:?} {:04x?}", mos6502.registers()),
+                BatchExit::Fault(exit) => {
+                    log::error!("{:04x?} {:04x?}", exit, mos6502.registers());
+                    anyhow::bail!("run error");
+                }
+            }
+
+            if dead_loop_iterations > args.dead_loop_iterations {
+                log::error!(
+                    "Dead loop with {:04x?} after {instructions_emulated} instructions",
+                    mos6502.registers()
+                );
+                anyhow::bail!("Dead loop for {dead_loop_iterations} iterations, aborting");
+            }
+        }
+
+        return Ok(());
+    }
+
     let mut instructions_emulated = 0;
     let mut prev_pc = !args.reset_pc;
     let mut dead_loop_iterations = 0;
     loop {
+        if log::log_enabled!(log::Level::Trace) {
+            let pc = mos6502.registers().pc();
+            if let Some(opcode) = mos6502.peek_u8(pc) {
+                let name = get_opcode_string_for(
+                    mos6502.variant(),
+                    mos6502.undocumented_opcodes(),
+                    opcode,
+                );
+                log::trace!("0x{pc:04x}: {name}");
+            }
+        }
+
         let run = mos6502.run();
         match run {
             Ok(RunExit::Executed(insn)) => {
@@ -157,6 +288,7 @@ fn main() -> anyhow::Result<()> {
 
                 if args.print_stats != 0 && instructions_emulated % args.print_stats == 0 {
                     log::info!("Instructions emulated: {instructions_emulated}");
+                    log::info!("Cycles elapsed: {}", mos6502.cycles());
                     log::info!("Last one: {insn:?}, {:04x?}", mos6502.registers());
                 }
             }
@@ -164,6 +296,13 @@ fn main() -> anyhow::Result<()> {
             Ok(RunExit::NonMaskableInterrupt) => {
                 log::debug!("Non-maskable interrupt {:04x?}", mos6502.registers())
             }
+            Ok(RunExit::Waiting) => log::trace!("Waiting for an interrupt"),
+            Ok(RunExit::Stalled(cycles)) => log::trace!("Stalled for {cycles} cycles"),
+            Ok(RunExit::Trapped(signature)) => log::debug!("Trap {signature:02x}"),
+            Ok(RunExit::Stopped) => {
+                log::info!("Processor stopped, {:04x?}", mos6502.registers());
+                break;
+            }
             Err(exit) => {
                 log::error!("{:04x?} {:04x?}", exit, mos6502.registers());
                 anyhow::bail!("run error");
@@ -178,6 +317,7 @@ fn main() -> anyhow::Result<()> {
         if pc == args.exit_pc {
             log::info!("Exiting as the program is at the exit PC 0x{pc:04x}",);
             log::info!("Instructions emulated: {instructions_emulated}");
+            log::info!("Cycles elapsed: {}", mos6502.cycles());
             log::info!("{:04x?}", mos6502.registers());
             break;
         }
@@ -1,15 +1,126 @@
-//! Very primitive BCD functions.
+//! Decimal mode arithmetic.
 //!
-//! No checks for the valid BCD represenation and no special types yet.
+//! Reproduces the results and the flags of ADC and SBC in the decimal mode
+//! for all operands, including the ones that are not valid BCD, as described
+//! in Bruce Clark's "Decimal Mode" tutorial at http://www.6502.org/tutorials/decimal_mode.html.
+//!
+//! On the NMOS 6502, only the carry flag and the result are meaningful.
+//! N and V come from the intermediate result of ADC, and Z is the same
+//! as in the binary mode. SBC sets all flags as in the binary mode.
+//! The 65C02 sets N and Z from the result.
+
+/// Result of the decimal addition or subtraction, and the flags set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BcdOutcome {
+    pub value: u8,
+    pub carry: bool,
+    pub overflow: bool,
+    pub negative: bool,
+    pub zero: bool,
+}
+
+/// Adds the digits, the carry from the low digit is adjusted to land
+/// on the high digit, and the high digit is left to be adjusted.
+/// Returns the intermediate sum and the signed overflow.
+fn add_digits(a: u8, b: u8, carry: bool) -> (u16, bool) {
+    let mut lo = (a & 0x0f) as u16 + (b & 0x0f) as u16 + carry as u16;
+    if lo >= 0x0a {
+        lo = ((lo + 0x06) & 0x0f) + 0x10;
+    }
+    let sum = (a & 0xf0) as u16 + (b & 0xf0) as u16 + lo;
+
+    let signed_sum = (a & 0xf0) as i8 as i16 + (b & 0xf0) as i8 as i16 + lo as i16;
+    let overflow = !(-128..=127).contains(&signed_sum);
+
+    (sum, overflow)
+}
+
+/// Adjusts the high digit of the intermediate sum.
+fn adjust_sum(sum: u16) -> (u8, bool) {
+    let sum = if sum >= 0xa0 { sum + 0x60 } else { sum };
+
+    (sum as u8, sum >= 0x100)
+}
+
+/// Subtracts in binary, the flags of the NMOS 6502 and the carry and
+/// the overflow of the 65C02 come from that.
+fn subtract_binary(a: u8, b: u8, carry: bool) -> (u8, bool, bool) {
+    let difference = a as i16 - b as i16 - !carry as i16;
+    let value = difference as u8;
+    let overflow = (a ^ b) & (a ^ value) & 0x80 != 0;
+
+    (value, difference >= 0, overflow)
+}
+
+/// ADC in the decimal mode of the NMOS 6502
+pub fn nmos_add(a: u8, b: u8, carry: bool) -> BcdOutcome {
+    let (sum, overflow) = add_digits(a, b, carry);
+    let (value, carry_out) = adjust_sum(sum);
 
-pub fn u8_to_bcd(u: u8) -> u8 {
-    if u < 100 {
-        ((u / 10) << 4) | (u % 10)
-    } else {
-        0x00
+    BcdOutcome {
+        value,
+        carry: carry_out,
+        overflow,
+        negative: sum & 0x80 != 0,
+        zero: a.wrapping_add(b).wrapping_add(carry as u8) == 0,
     }
 }
 
-pub fn bcd_to_u8(bcd: u8) -> u8 {
-    (bcd >> 4) * 10 + (bcd & 0x0f)
+/// ADC in the decimal mode of the 65C02
+pub fn cmos_add(a: u8, b: u8, carry: bool) -> BcdOutcome {
+    let (sum, overflow) = add_digits(a, b, carry);
+    let (value, carry_out) = adjust_sum(sum);
+
+    BcdOutcome {
+        value,
+        carry: carry_out,
+        overflow,
+        negative: value & 0x80 != 0,
+        zero: value == 0,
+    }
+}
+
+/// SBC in the decimal mode of the NMOS 6502
+pub fn nmos_sub(a: u8, b: u8, carry: bool) -> BcdOutcome {
+    let borrow = !carry as i16;
+    let mut lo = (a & 0x0f) as i16 - (b & 0x0f) as i16 - borrow;
+    if lo < 0 {
+        lo = ((lo - 0x06) & 0x0f) - 0x10;
+    }
+    let mut difference = (a & 0xf0) as i16 - (b & 0xf0) as i16 + lo;
+    if difference < 0 {
+        difference -= 0x60;
+    }
+
+    let (binary, carry_out, overflow) = subtract_binary(a, b, carry);
+    BcdOutcome {
+        value: difference as u8,
+        carry: carry_out,
+        overflow,
+        negative: binary & 0x80 != 0,
+        zero: binary == 0,
+    }
+}
+
+/// SBC in the decimal mode of the 65C02
+pub fn cmos_sub(a: u8, b: u8, carry: bool) -> BcdOutcome {
+    let borrow = !carry as i16;
+    let lo = (a & 0x0f) as i16 - (b & 0x0f) as i16 - borrow;
+    let mut difference = a as i16 - b as i16 - borrow;
+    if difference < 0 {
+        difference -= 0x60;
+    }
+    if lo < 0 {
+        difference -= 0x06;
+    }
+
+    let value = difference as u8;
+    let (_, carry_out, overflow) = subtract_binary(a, b, carry);
+    BcdOutcome {
+        value,
+        carry: carry_out,
+        overflow,
+        negative: value & 0x80 != 0,
+        zero: value == 0,
+    }
 }
@@ -1,156 +1,162 @@
 //! Instruction Data
 
+use crate::yamos6502::CpuVariant;
+use crate::yamos6502::UndocumentedOpcodes;
+
 /// String representation for instructions.
 ///
 /// Each 8-bit opcode below is split into 6 bits that
 /// represent Operation and Adressing Mode, and 2 bits that
 /// indicate the instruction group.
+///
+/// The stable "undocumented" instructions are listed by their
+/// commonly used names, the unstable ones are listed as JAM.
 const INSN_STR: [&str; 256] = [
     // Mnemonic     Opcode
     "BRK",       // 0x00    0b000000    0b00
     "ORA X,ind", // 0x01    0b000000    0b01
     "JAM",       // 0x02    0b000000    0b10
-    "JAM",       // 0x03    0b000000    0b11
-    "JAM",       // 0x04    0b000001    0b00
+    "SLO X,ind", // 0x03    0b000000    0b11
+    "NOP zpg",   // 0x04    0b000001    0b00
     "ORA zpg",   // 0x05    0b000001    0b01
     "ASL zpg",   // 0x06    0b000001    0b10
-    "JAM",       // 0x07    0b000001    0b11
+    "SLO zpg",   // 0x07    0b000001    0b11
     "PHP",       // 0x08    0b000010    0b00
     "ORA imm",   // 0x09    0b000010    0b01
     "ASL",       // 0x0a    0b000010    0b10
-    "JAM",       // 0x0b    0b000010    0b11
-    "JAM",       // 0x0c    0b000011    0b00
+    "ANC imm",   // 0x0b    0b000010    0b11
+    "NOP abs",   // 0x0c    0b000011    0b00
     "ORA abs",   // 0x0d    0b000011    0b01
     "ASL abs",   // 0x0e    0b000011    0b10
-    "JAM",       // 0x0f    0b000011    0b11
+    "SLO abs",   // 0x0f    0b000011    0b11
     "BPL rel",   // 0x10    0b000100    0b00
     "ORA ind,Y", // 0x11    0b000100    0b01
     "JAM",       // 0x12    0b000100    0b10
-    "JAM",       // 0x13    0b000100    0b11
-    "JAM",       // 0x14    0b000101    0b00
+    "SLO ind,Y", // 0x13    0b000100    0b11
+    "NOP zpg,X", // 0x14    0b000101    0b00
     "ORA zpg,X", // 0x15    0b000101    0b01
     "ASL zpg,X", // 0x16    0b000101    0b10
-    "JAM",       // 0x17    0b000101    0b11
+    "SLO zpg,X", // 0x17    0b000101    0b11
     "CLC",       // 0x18    0b000110    0b00
     "ORA abs,Y", // 0x19    0b000110    0b01
-    "JAM",       // 0x1a    0b000110    0b10
-    "JAM",       // 0x1b    0b000110    0b11
-    "JAM",       // 0x1c    0b000111    0b00
+    "NOP",       // 0x1a    0b000110    0b10
+    "SLO abs,Y", // 0x1b    0b000110    0b11
+    "NOP abs,X", // 0x1c    0b000111    0b00
     "ORA abs,X", // 0x1d    0b000111    0b01
     "ASL abs,X", // 0x1e    0b000111    0b10
-    "JAM",       // 0x1f    0b000111    0b11
+    "SLO abs,X", // 0x1f    0b000111    0b11
     "JSR abs",   // 0x20    0b001000    0b00
     "AND X,ind", // 0x21    0b001000    0b01
     "JAM",       // 0x22    0b001000    0b10
-    "JAM",       // 0x23    0b001000    0b11
+    "RLA X,ind", // 0x23    0b001000    0b11
     "BIT zpg",   // 0x24    0b001001    0b00
     "AND zpg",   // 0x25    0b001001    0b01
     "ROL zpg",   // 0x26    0b001001    0b10
-    "JAM",       // 0x27    0b001001    0b11
+    "RLA zpg",   // 0x27    0b001001    0b11
     "PLP",       // 0x28    0b001010    0b00
     "AND imm",   // 0x29    0b001010    0b01
     "ROL",       // 0x2a    0b001010    0b10
-    "JAM",       // 0x2b    0b001010    0b11
+    "ANC imm",   // 0x2b    0b001010    0b11
     "BIT abs",   // 0x2c    0b001011    0b00
     "AND abs",   // 0x2d    0b001011    0b01
     "ROL abs",   // 0x2e    0b001011    0b10
-    "JAM",       // 0x2f    0b001011    0b11
+    "RLA abs",   // 0x2f    0b001011    0b11
     "BMI rel",   // 0x30    0b001100    0b00
     "AND ind,Y", // 0x31    0b001100    0b01
     "JAM",       // 0x32    0b001100    0b10
-    "JAM",       // 0x33    0b001100    0b11
-    "JAM",       // 0x34    0b001101    0b00
+    "RLA ind,Y", // 0x33    0b001100    0b11
+    "NOP zpg,X", // 0x34    0b001101    0b00
     "AND zpg,X", // 0x35    0b001101    0b01
     "ROL zpg,X", // 0x36    0b001101    0b10
-    "JAM",       // 0x37    0b001101    0b11
+    "RLA zpg,X", // 0x37    0b001101    0b11
     "SEC",       // 0x38    0b001110    0b00
     "AND abs,Y", // 0x39    0b001110    0b01
-    "JAM",       // 0x3a    0b001110    0b10
-    "JAM",       // 0x3b    0b001110    0b11
-    "JAM",       // 0x3c    0b001111    0b00
+    "NOP",       // 0x3a    0b001110    0b10
+    "RLA abs,Y", // 0x3b    0b001110    0b11
+    "NOP abs,X", // 0x3c    0b001111    0b00
     "AND abs,X", // 0x3d    0b001111    0b01
     "ROL abs,X", // 0x3e    0b001111    0b10
-    "JAM",       // 0x3f    0b001111    0b11
+    "RLA abs,X", // 0x3f    0b001111    0b11
     "RTI",       // 0x40    0b010000    0b00
     "EOR X,ind", // 0x41    0b010000    0b01
     "JAM",       // 0x42    0b010000    0b10
-    "JAM",       // 0x43    0b010000    0b11
-    "JAM",       // 0x44    0b010001    0b00
+    "SRE X,ind", // 0x43    0b010000    0b11
+    "NOP zpg",   // 0x44    0b010001    0b00
     "EOR zpg",   // 0x45    0b010001    0b01
     "LSR zpg",   // 0x46    0b010001    0b10
-    "JAM",       // 0x47    0b010001    0b11
+    "SRE zpg",   // 0x47    0b010001    0b11
     "PHA",       // 0x48    0b010010    0b00
     "EOR imm",   // 0x49    0b010010    0b01
     "LSR",       // 0x4a    0b010010    0b10
-    "JAM",       // 0x4b    0b010010    0b11
+    "ALR imm",   // 0x4b    0b010010    0b11
     "JMP abs",   // 0x4c    0b010011    0b00
     "EOR abs",   // 0x4d    0b010011    0b01
     "LSR abs",   // 0x4e    0b010011    0b10
-    "JAM",       // 0x4f    0b010011    0b11
+    "SRE abs",   // 0x4f    0b010011    0b11
     "BVC rel",   // 0x50    0b010100    0b00
     "EOR ind,Y", // 0x51    0b010100    0b01
     "JAM",       // 0x52    0b010100    0b10
-    "JAM",       // 0x53    0b010100    0b11
-    "JAM",       // 0x54    0b010101    0b00
+    "SRE ind,Y", // 0x53    0b010100    0b11
+    "NOP zpg,X", // 0x54    0b010101    0b00
     "EOR zpg,X", // 0x55    0b010101    0b01
     "LSR zpg,X", // 0x56    0b010101    0b10
-    "JAM",       // 0x57    0b010101    0b11
+    "SRE zpg,X", // 0x57    0b010101    0b11
     "CLI",       // 0x58    0b010110    0b00
     "EOR abs,Y", // 0x59    0b010110    0b01
-    "JAM",       // 0x5a    0b010110    0b10
-    "JAM",       // 0x5b    0b010110    0b11
-    "JAM",       // 0x5c    0b010111    0b00
+    "NOP",       // 0x5a    0b010110    0b10
+    "SRE abs,Y", // 0x5b    0b010110    0b11
+    "NOP abs,X", // 0x5c    0b010111    0b00
     "EOR abs,X", // 0x5d    0b010111    0b01
     "LSR abs,X", // 0x5e    0b010111    0b10
-    "JAM",       // 0x5f    0b010111    0b11
+    "SRE abs,X", // 0x5f    0b010111    0b11
     "RTS",       // 0x60    0b011000    0b00
     "ADC X,ind", // 0x61    0b011000    0b01
     "JAM",       // 0x62    0b011000    0b10
-    "JAM",       // 0x63    0b011000    0b11
-    "JAM",       // 0x64    0b011001    0b00
+    "RRA X,ind", // 0x63    0b011000    0b11
+    "NOP zpg",   // 0x64    0b011001    0b00
     "ADC zpg",   // 0x65    0b011001    0b01
     "ROR zpg",   // 0x66    0b011001    0b10
-    "JAM",       // 0x67    0b011001    0b11
+    "RRA zpg",   // 0x67    0b011001    0b11
     "PLA",       // 0x68    0b011010    0b00
     "ADC imm",   // 0x69    0b011010    0b01
     "ROR",       // 0x6a    0b011010    0b10
-    "JAM",       // 0x6b    0b011010    0b11
+    "ARR imm",   // 0x6b    0b011010    0b11
     "JMP ind",   // 0x6c    0b011011    0b00
     "ADC abs",   // 0x6d    0b011011    0b01
     "ROR abs",   // 0x6e    0b011011    0b10
-    "JAM",       // 0x6f    0b011011    0b11
+    "RRA abs",   // 0x6f    0b011011    0b11
     "BVS rel",   // 0x70    0b011100    0b00
     "ADC ind,Y", // 0x71    0b011100    0b01
     "JAM",       // 0x72    0b011100    0b10
-    "JAM",       // 0x73    0b011100    0b11
-    "JAM",       // 0x74    0b011101    0b00
+    "RRA ind,Y", // 0x73    0b011100    0b11
+    "NOP zpg,X", // 0x74    0b011101    0b00
     "ADC zpg,X", // 0x75    0b011101    0b01
     "ROR zpg,X", // 0x76    0b011101    0b10
-    "JAM",       // 0x77    0b011101    0b11
+    "RRA zpg,X", // 0x77    0b011101    0b11
     "SEI",       // 0x78    0b011110    0b00
     "ADC abs,Y", // 0x79    0b011110    0b01
-    "JAM",       // 0x7a    0b011110    0b10
-    "JAM",       // 0x7b    0b011110    0b11
-    "JAM",       // 0x7c    0b011111    0b00
+    "NOP",       // 0x7a    0b011110    0b10
+    "RRA abs,Y", // 0x7b    0b011110    0b11
+    "NOP abs,X", // 0x7c    0b011111    0b00
     "ADC abs,X", // 0x7d    0b011111    0b01
     "ROR abs,X", // 0x7e    0b011111    0b10
-    "JAM",       // 0x7f    0b011111    0b11
-    "JAM",       // 0x80    0b100000    0b00
+    "RRA abs,X", // 0x7f    0b011111    0b11
+    "NOP imm",   // 0x80    0b100000    0b00
     "STA X,ind", // 0x81    0b100000    0b01
-    "JAM",       // 0x82    0b100000    0b10
-    "JAM",       // 0x83    0b100000    0b11
+    "NOP imm",   // 0x82    0b100000    0b10
+    "SAX X,ind", // 0x83    0b100000    0b11
     "STY zpg",   // 0x84    0b100001    0b00
     "STA zpg",   // 0x85    0b100001    0b01
     "STX zpg",   // 0x86    0b100001    0b10
-    "JAM",       // 0x87    0b100001    0b11
+    "SAX zpg",   // 0x87    0b100001    0b11
     "DEY",       // 0x88    0b100010    0b00
-    "JAM",       // 0x89    0b100010    0b01
+    "NOP imm",   // 0x89    0b100010    0b01
     "TXA",       // 0x8a    0b100010    0b10
     "JAM",       // 0x8b    0b100010    0b11
     "STY abs",   // 0x8c    0b100011    0b00
     "STA abs",   // 0x8d    0b100011    0b01
     "STX abs",   // 0x8e    0b100011    0b10
-    "JAM",       // 0x8f    0b100011    0b11
+    "SAX abs",   // 0x8f    0b100011    0b11
     "BCC rel",   // 0x90    0b100100    0b00
     "STA ind,Y", // 0x91    0b100100    0b01
     "JAM",       // 0x92    0b100100    0b10
@@ -158,7 +164,7 @@ const INSN_STR: [&str; 256] = [
     "STY zpg,X", // 0x94    0b100101    0b00
     "STA zpg,X", // 0x95    0b100101    0b01
     "STX zpg,Y", // 0x96    0b100101    0b10
-    "JAM",       // 0x97    0b100101    0b11
+    "SAX zpg,Y", // 0x97    0b100101    0b11
     "TYA",       // 0x98    0b100110    0b00
     "STA abs,Y", // 0x99    0b100110    0b01
     "TXS",       // 0x9a    0b100110    0b10
@@ -170,11 +176,11 @@ const INSN_STR: [&str; 256] = [
     "LDY imm",   // 0xa0    0b101000    0b00
     "LDA X,ind", // 0xa1    0b101000    0b01
     "LDX imm",   // 0xa2    0b101000    0b10
-    "JAM",       // 0xa3    0b101000    0b11
+    "LAX X,ind", // 0xa3    0b101000    0b11
     "LDY zpg",   // 0xa4    0b101001    0b00
     "LDA zpg",   // 0xa5    0b101001    0b01
     "LDX zpg",   // 0xa6    0b101001    0b10
-    "JAM",       // 0xa7    0b101001    0b11
+    "LAX zpg",   // 0xa7    0b101001    0b11
     "TAY",       // 0xa8    0b101010    0b00
     "LDA imm",   // 0xa9    0b101010    0b01
     "TAX",       // 0xaa    0b101010    0b10
@@ -182,15 +188,15 @@ const INSN_STR: [&str; 256] = [
     "LDY abs",   // 0xac    0b101011    0b00
     "LDA abs",   // 0xad    0b101011    0b01
     "LDX abs",   // 0xae    0b101011    0b10
-    "JAM",       // 0xaf    0b101011    0b11
+    "LAX abs",   // 0xaf    0b101011    0b11
     "BCS rel",   // 0xb0    0b101100    0b00
     "LDA ind,Y", // 0xb1    0b101100    0b01
     "JAM",       // 0xb2    0b101100    0b10
-    "JAM",       // 0xb3    0b101100    0b11
+    "LAX ind,Y", // 0xb3    0b101100    0b11
     "LDY zpg,X", // 0xb4    0b101101    0b00
     "LDA zpg,X", // 0xb5    0b101101    0b01
     "LDX zpg,Y", // 0xb6    0b101101    0b10
-    "JAM",       // 0xb7    0b101101    0b11
+    "LAX zpg,Y", // 0xb7    0b101101    0b11
     "CLV",       // 0xb8    0b101110    0b00
     "LDA abs,Y", // 0xb9    0b101110    0b01
     "TSX",       // 0xba    0b101110    0b10
@@ -198,71 +204,391 @@ const INSN_STR: [&str; 256] = [
     "LDY abs,X", // 0xbc    0b101111    0b00
     "LDA abs,X", // 0xbd    0b101111    0b01
     "LDX abs,Y", // 0xbe    0b101111    0b10
-    "JAM",       // 0xbf    0b101111    0b11
+    "LAX abs,Y", // 0xbf    0b101111    0b11
     "CPY imm",   // 0xc0    0b110000    0b00
     "CMP X,ind", // 0xc1    0b110000    0b01
-    "JAM",       // 0xc2    0b110000    0b10
-    "JAM",       // 0xc3    0b110000    0b11
+    "NOP imm",   // 0xc2    0b110000    0b10
+    "DCP X,ind", // 0xc3    0b110000    0b11
     "CPY zpg",   // 0xc4    0b110001    0b00
     "CMP zpg",   // 0xc5    0b110001    0b01
     "DEC zpg",   // 0xc6    0b110001    0b10
-    "JAM",       // 0xc7    0b110001    0b11
+    "DCP zpg",   // 0xc7    0b110001    0b11
     "INY",       // 0xc8    0b110010    0b00
     "CMP imm",   // 0xc9    0b110010    0b01
     "DEX",       // 0xca    0b110010    0b10
-    "JAM",       // 0xcb    0b110010    0b11
+    "SBX imm",   // 0xcb    0b110010    0b11
     "CPY abs",   // 0xcc    0b110011    0b00
     "CMP abs",   // 0xcd    0b110011    0b01
     "DEC abs",   // 0xce    0b110011    0b10
-    "JAM",       // 0xcf    0b110011    0b11
+    "DCP abs",   // 0xcf    0b110011    0b11
     "BNE rel",   // 0xd0    0b110100    0b00
     "CMP ind,Y", // 0xd1    0b110100    0b01
     "JAM",       // 0xd2    0b110100    0b10
-    "JAM",       // 0xd3    0b110100    0b11
-    "JAM",       // 0xd4    0b110101    0b00
+    "DCP ind,Y", // 0xd3    0b110100    0b11
+    "NOP zpg,X", // 0xd4    0b110101    0b00
     "CMP zpg,X", // 0xd5    0b110101    0b01
     "DEC zpg,X", // 0xd6    0b110101    0b10
-    "JAM",       // 0xd7    0b110101    0b11
+    "DCP zpg,X", // 0xd7    0b110101    0b11
     "CLD",       // 0xd8    0b110110    0b00
     "CMP abs,Y", // 0xd9    0b110110    0b01
-    "JAM",       // 0xda    0b110110    0b10
-    "JAM",       // 0xdb    0b110110    0b11
-    "JAM",       // 0xdc    0b110111    0b00
+    "NOP",       // 0xda    0b110110    0b10
+    "DCP abs,Y", // 0xdb    0b110110    0b11
+    "NOP abs,X", // 0xdc    0b110111    0b00
     "CMP abs,X", // 0xdd    0b110111    0b01
     "DEC abs,X", // 0xde    0b110111    0b10
-    "JAM",       // 0xdf    0b110111    0b11
+    "DCP abs,X", // 0xdf    0b110111    0b11
     "CPX imm",   // 0xe0    0b111000    0b00
     "SBC X,ind", // 0xe1    0b111000    0b01
-    "JAM",       // 0xe2    0b111000    0b10
-    "JAM",       // 0xe3    0b111000    0b11
+    "NOP imm",   // 0xe2    0b111000    0b10
+    "ISC X,ind", // 0xe3    0b111000    0b11
     "CPX zpg",   // 0xe4    0b111001    0b00
     "SBC zpg",   // 0xe5    0b111001    0b01
     "INC zpg",   // 0xe6    0b111001    0b10
-    "JAM",       // 0xe7    0b111001    0b11
+    "ISC zpg",   // 0xe7    0b111001    0b11
     "INX",       // 0xe8    0b111010    0b00
     "SBC imm",   // 0xe9    0b111010    0b01
     "NOP",       // 0xea    0b111010    0b10
-    "JAM",       // 0xeb    0b111010    0b11
+    "SBC imm",   // 0xeb    0b111010    0b11
     "CPX abs",   // 0xec    0b111011    0b00
     "SBC abs",   // 0xed    0b111011    0b01
     "INC abs",   // 0xee    0b111011    0b10
-    "JAM",       // 0xef    0b111011    0b11
+    "ISC abs",   // 0xef    0b111011    0b11
     "BEQ rel",   // 0xf0    0b111100    0b00
     "SBC ind,Y", // 0xf1    0b111100    0b01
     "JAM",       // 0xf2    0b111100    0b10
-    "JAM",       // 0xf3    0b111100    0b11
-    "JAM",       // 0xf4    0b111101    0b00
+    "ISC ind,Y", // 0xf3    0b111100    0b11
+    "NOP zpg,X", // 0xf4    0b111101    0b00
     "SBC zpg,X", // 0xf5    0b111101    0b01
     "INC zpg,X", // 0xf6    0b111101    0b10
-    "JAM",       // 0xf7    0b111101    0b11
+    "ISC zpg,X", // 0xf7    0b111101    0b11
     "SED",       // 0xf8    0b111110    0b00
     "SBC abs,Y", // 0xf9    0b111110    0b01
-    "JAM",       // 0xfa    0b111110    0b10
-    "JAM",       // 0xfb    0b111110    0b11
-    "JAM",       // 0xfc    0b111111    0b00
+    "NOP",       // 0xfa    0b111110    0b10
+    "ISC abs,Y", // 0xfb    0b111110    0b11
+    "NOP abs,X", // 0xfc    0b111111    0b00
     "SBC abs,X", // 0xfd    0b111111    0b01
     "INC abs,X", // 0xfe    0b111111    0b10
-    "JAM",       // 0xff    0b111111    0b11
+    "ISC abs,X", // 0xff    0b111111    0b11
+];
+
+/// String representation for the WDC 65C02 instructions.
+///
+/// The opcodes left undefined are listed as NOPs, the `(zp)` and
+/// `(abs,X)` addressing modes as `(zpg)` and `(abs,X)`. The bit number
+/// of RMB, SMB, BBR and BBS is a part of the mnemonic.
+const INSN_STR_65C02: [&str; 256] = [
+    // Mnemonic      Opcode
+    "BRK",          // 0x00    0b000000    0b00
+    "ORA X,ind",    // 0x01    0b000000    0b01
+    "NOP imm",      // 0x02    0b000000    0b10
+    "NOP",          // 0x03    0b000000    0b11
+    "TSB zpg",      // 0x04    0b000001    0b00
+    "ORA zpg",      // 0x05    0b000001    0b01
+    "ASL zpg",      // 0x06    0b000001    0b10
+    "RMB0 zpg",     // 0x07    0b000001    0b11
+    "PHP",          // 0x08    0b000010    0b00
+    "ORA imm",      // 0x09    0b000010    0b01
+    "ASL",          // 0x0a    0b000010    0b10
+    "NOP",          // 0x0b    0b000010    0b11
+    "TSB abs",      // 0x0c    0b000011    0b00
+    "ORA abs",      // 0x0d    0b000011    0b01
+    "ASL abs",      // 0x0e    0b000011    0b10
+    "BBR0 zpg,rel", // 0x0f    0b000011    0b11
+    "BPL rel",      // 0x10    0b000100    0b00
+    "ORA ind,Y",    // 0x11    0b000100    0b01
+    "ORA (zpg)",    // 0x12    0b000100    0b10
+    "NOP",          // 0x13    0b000100    0b11
+    "TRB zpg",      // 0x14    0b000101    0b00
+    "ORA zpg,X",    // 0x15    0b000101    0b01
+    "ASL zpg,X",    // 0x16    0b000101    0b10
+    "RMB1 zpg",     // 0x17    0b000101    0b11
+    "CLC",          // 0x18    0b000110    0b00
+    "ORA abs,Y",    // 0x19    0b000110    0b01
+    "INC",          // 0x1a    0b000110    0b10
+    "NOP",          // 0x1b    0b000110    0b11
+    "TRB abs",      // 0x1c    0b000111    0b00
+    "ORA abs,X",    // 0x1d    0b000111    0b01
+    "ASL abs,X",    // 0x1e    0b000111    0b10
+    "BBR1 zpg,rel", // 0x1f    0b000111    0b11
+    "JSR abs",      // 0x20    0b001000    0b00
+    "AND X,ind",    // 0x21    0b001000    0b01
+    "NOP imm",      // 0x22    0b001000    0b10
+    "NOP",          // 0x23    0b001000    0b11
+    "BIT zpg",      // 0x24    0b001001    0b00
+    "AND zpg",      // 0x25    0b001001    0b01
+    "ROL zpg",      // 0x26    0b001001    0b10
+    "RMB2 zpg",     // 0x27    0b001001    0b11
+    "PLP",          // 0x28    0b001010    0b00
+    "AND imm",      // 0x29    0b001010    0b01
+    "ROL",          // 0x2a    0b001010    0b10
+    "NOP",          // 0x2b    0b001010    0b11
+    "BIT abs",      // 0x2c    0b001011    0b00
+    "AND abs",      // 0x2d    0b001011    0b01
+    "ROL abs",      // 0x2e    0b001011    0b10
+    "BBR2 zpg,rel", // 0x2f    0b001011    0b11
+    "BMI rel",      // 0x30    0b001100    0b00
+    "AND ind,Y",    // 0x31    0b001100    0b01
+    "AND (zpg)",    // 0x32    0b001100    0b10
+    "NOP",          // 0x33    0b001100    0b11
+    "BIT zpg,X",    // 0x34    0b001101    0b00
+    "AND zpg,X",    // 0x35    0b001101    0b01
+    "ROL zpg,X",    // 0x36    0b001101    0b10
+    "RMB3 zpg",     // 0x37    0b001101    0b11
+    "SEC",          // 0x38    0b001110    0b00
+    "AND abs,Y",    // 0x39    0b001110    0b01
+    "DEC",          // 0x3a    0b001110    0b10
+    "NOP",          // 0x3b    0b001110    0b11
+    "BIT abs,X",    // 0x3c    0b001111    0b00
+    "AND abs,X",    // 0x3d    0b001111    0b01
+    "ROL abs,X",    // 0x3e    0b001111    0b10
+    "BBR3 zpg,rel", // 0x3f    0b001111    0b11
+    "RTI",          // 0x40    0b010000    0b00
+    "EOR X,ind",    // 0x41    0b010000    0b01
+    "NOP imm",      // 0x42    0b010000    0b10
+    "NOP",          // 0x43    0b010000    0b11
+    "NOP zpg",      // 0x44    0b010001    0b00
+    "EOR zpg",      // 0x45    0b010001    0b01
+    "LSR zpg",      // 0x46    0b010001    0b10
+    "RMB4 zpg",     // 0x47    0b010001    0b11
+    "PHA",          // 0x48    0b010010    0b00
+    "EOR imm",      // 0x49    0b010010    0b01
+    "LSR",          // 0x4a    0b010010    0b10
+    "NOP",          // 0x4b    0b010010    0b11
+    "JMP abs",      // 0x4c    0b010011    0b00
+    "EOR abs",      // 0x4d    0b010011    0b01
+    "LSR abs",      // 0x4e    0b010011    0b10
+    "BBR4 zpg,rel", // 0x4f    0b010011    0b11
+    "BVC rel",      // 0x50    0b010100    0b00
+    "EOR ind,Y",    // 0x51    0b010100    0b01
+    "EOR (zpg)",    // 0x52    0b010100    0b10
+    "NOP",          // 0x53    0b010100    0b11
+    "NOP zpg,X",    // 0x54    0b010101    0b00
+    "EOR zpg,X",    // 0x55    0b010101    0b01
+    "LSR zpg,X",    // 0x56    0b010101    0b10
+    "RMB5 zpg",     // 0x57    0b010101    0b11
+    "CLI",          // 0x58    0b010110    0b00
+    "EOR abs,Y",    // 0x59    0b010110    0b01
+    "PHY",          // 0x5a    0b010110    0b10
+    "NOP",          // 0x5b    0b010110    0b11
+    "NOP abs",      // 0x5c    0b010111    0b00
+    "EOR abs,X",    // 0x5d    0b010111    0b01
+    "LSR abs,X",    // 0x5e    0b010111    0b10
+    "BBR5 zpg,rel", // 0x5f    0b010111    0b11
+    "RTS",          // 0x60    0b011000    0b00
+    "ADC X,ind",    // 0x61    0b011000    0b01
+    "NOP imm",      // 0x62    0b011000    0b10
+    "NOP",          // 0x63    0b011000    0b11
+    "STZ zpg",      // 0x64    0b011001    0b00
+    "ADC zpg",      // 0x65    0b011001    0b01
+    "ROR zpg",      // 0x66    0b011001    0b10
+    "RMB6 zpg",     // 0x67    0b011001    0b11
+    "PLA",          // 0x68    0b011010    0b00
+    "ADC imm",      // 0x69    0b011010    0b01
+    "ROR",          // 0x6a    0b011010    0b10
+    "NOP",          // 0x6b    0b011010    0b11
+    "JMP ind",      // 0x6c    0b011011    0b00
+    "ADC abs",      // 0x6d    0b011011    0b01
+    "ROR abs",      // 0x6e    0b011011    0b10
+    "BBR6 zpg,rel", // 0x6f    0b011011    0b11
+    "BVS rel",      // 0x70    0b011100    0b00
+    "ADC ind,Y",    // 0x71    0b011100    0b01
+    "ADC (zpg)",    // 0x72    0b011100    0b10
+    "NOP",          // 0x73    0b011100    0b11
+    "STZ zpg,X",    // 0x74    0b011101    0b00
+    "ADC zpg,X",    // 0x75    0b011101    0b01
+    "ROR zpg,X",    // 0x76    0b011101    0b10
+    "RMB7 zpg",     // 0x77    0b011101    0b11
+    "SEI",          // 0x78    0b011110    0b00
+    "ADC abs,Y",    // 0x79    0b011110    0b01
+    "PLY",          // 0x7a    0b011110    0b10
+    "NOP",          // 0x7b    0b011110    0b11
+    "JMP (abs,X)",  // 0x7c    0b011111    0b00
+    "ADC abs,X",    // 0x7d    0b011111    0b01
+    "ROR abs,X",    // 0x7e    0b011111    0b10
+    "BBR7 zpg,rel", // 0x7f    0b011111    0b11
+    "BRA rel",      // 0x80    0b100000    0b00
+    "STA X,ind",    // 0x81    0b100000    0b01
+    "NOP imm",      // 0x82    0b100000    0b10
+    "NOP",          // 0x83    0b100000    0b11
+    "STY zpg",      // 0x84    0b100001    0b00
+    "STA zpg",      // 0x85    0b100001    0b01
+    "STX zpg",      // 0x86    0b100001    0b10
+    "SMB0 zpg",     // 0x87    0b100001    0b11
+    "DEY",          // 0x88    0b100010    0b00
+    "BIT imm",      // 0x89    0b100010    0b01
+    "TXA",          // 0x8a    0b100010    0b10
+    "NOP",          // 0x8b    0b100010    0b11
+    "STY abs",      // 0x8c    0b100011    0b00
+    "STA abs",      // 0x8d    0b100011    0b01
+    "STX abs",      // 0x8e    0b100011    0b10
+    "BBS0 zpg,rel", // 0x8f    0b100011    0b11
+    "BCC rel",      // 0x90    0b100100    0b00
+    "STA ind,Y",    // 0x91    0b100100    0b01
+    "STA (zpg)",    // 0x92    0b100100    0b10
+    "NOP",          // 0x93    0b100100    0b11
+    "STY zpg,X",    // 0x94    0b100101    0b00
+    "STA zpg,X",    // 0x95    0b100101    0b01
+    "STX zpg,Y",    // 0x96    0b100101    0b10
+    "SMB1 zpg",     // 0x97    0b100101    0b11
+    "TYA",          // 0x98    0b100110    0b00
+    "STA abs,Y",    // 0x99    0b100110    0b01
+    "TXS",          // 0x9a    0b100110    0b10
+    "NOP",          // 0x9b    0b100110    0b11
+    "STZ abs",      // 0x9c    0b100111    0b00
+    "STA abs,X",    // 0x9d    0b100111    0b01
+    "STZ abs,X",    // 0x9e    0b100111    0b10
+    "BBS1 zpg,rel", // 0x9f    0b100111    0b11
+    "LDY imm",      // 0xa0    0b101000    0b00
+    "LDA X,ind",    // 0xa1    0b101000    0b01
+    "LDX imm",      // 0xa2    0b101000    0b10
+    "NOP",          // 0xa3    0b101000    0b11
+    "LDY zpg",      // 0xa4    0b101001    0b00
+    "LDA zpg",      // 0xa5    0b101001    0b01
+    "LDX zpg",      // 0xa6    0b101001    0b10
+    "SMB2 zpg",     // 0xa7    0b101001    0b11
+    "TAY",          // 0xa8    0b101010    0b00
+    "LDA imm",      // 0xa9    0b101010    0b01
+    "TAX",          // 0xaa    0b101010    0b10
+    "NOP",          // 0xab    0b101010    0b11
+    "LDY abs",      // 0xac    0b101011    0b00
+    "LDA abs",      // 0xad    0b101011    0b01
+    "LDX abs",      // 0xae    0b101011    0b10
+    "BBS2 zpg,rel", // 0xaf    0b101011    0b11
+    "BCS rel",      // 0xb0    0b101100    0b00
+    "LDA ind,Y",    // 0xb1    0b101100    0b01
+    "LDA (zpg)",    // 0xb2    0b101100    0b10
+    "NOP",          // 0xb3    0b101100    0b11
+    "LDY zpg,X",    // 0xb4    0b101101    0b00
+    "LDA zpg,X",    // 0xb5    0b101101    0b01
+    "LDX zpg,Y",    // 0xb6    0b101101    0b10
+    "SMB3 zpg",     // 0xb7    0b101101    0b11
+    "CLV",          // 0xb8    0b101110    0b00
+    "LDA abs,Y",    // 0xb9    0b101110    0b01
+    "TSX",          // 0xba    0b101110    0b10
+    "NOP",          // 0xbb    0b101110    0b11
+    "LDY abs,X",    // 0xbc    0b101111    0b00
+    "LDA abs,X",    // 0xbd    0b101111    0b01
+    "LDX abs,Y",    // 0xbe    0b101111    0b10
+    "BBS3 zpg,rel", // 0xbf    0b101111    0b11
+    "CPY imm",      // 0xc0    0b110000    0b00
+    "CMP X,ind",    // 0xc1    0b110000    0b01
+    "NOP imm",      // 0xc2    0b110000    0b10
+    "NOP",          // 0xc3    0b110000    0b11
+    "CPY zpg",      // 0xc4    0b110001    0b00
+    "CMP zpg",      // 0xc5    0b110001    0b01
+    "DEC zpg",      // 0xc6    0b110001    0b10
+    "SMB4 zpg",     // 0xc7    0b110001    0b11
+    "INY",          // 0xc8    0b110010    0b00
+    "CMP imm",      // 0xc9    0b110010    0b01
+    "DEX",          // 0xca    0b110010    0b10
+    "WAI",          // 0xcb    0b110010    0b11
+    "CPY abs",      // 0xcc    0b110011    0b00
+    "CMP abs",      // 0xcd    0b110011    0b01
+    "DEC abs",      // 0xce    0b110011    0b10
+    "BBS4 zpg,rel", // 0xcf    0b110011    0b11
+    "BNE rel",      // 0xd0    0b110100    0b00
+    "CMP ind,Y",    // 0xd1    0b110100    0b01
+    "CMP (zpg)",    // 0xd2    0b110100    0b10
+    "NOP",          // 0xd3    0b110100    0b11
+    "NOP zpg,X",    // 0xd4    0b110101    0b00
+    "CMP zpg,X",    // 0xd5    0b110101    0b01
+    "DEC zpg,X",    // 0xd6    0b110101    0b10
+    "SMB5 zpg",     // 0xd7    0b110101    0b11
+    "CLD",          // 0xd8    0b110110    0b00
+    "CMP abs,Y",    // 0xd9    0b110110    0b01
+    "PHX",          // 0xda    0b110110    0b10
+    "STP",          // 0xdb    0b110110    0b11
+    "NOP abs",      // 0xdc    0b110111    0b00
+    "CMP abs,X",    // 0xdd    0b110111    0b01
+    "DEC abs,X",    // 0xde    0b110111    0b10
+    "BBS5 zpg,rel", // 0xdf    0b110111    0b11
+    "CPX imm",      // 0xe0    0b111000    0b00
+    "SBC X,ind",    // 0xe1    0b111000    0b01
+    "NOP imm",      // 0xe2    0b111000    0b10
+    "NOP",          // 0xe3    0b111000    0b11
+    "CPX zpg",      // 0xe4    0b111001    0b00
+    "SBC zpg",      // 0xe5    0b111001    0b01
+    "INC zpg",      // 0xe6    0b111001    0b10
+    "SMB6 zpg",     // 0xe7    0b111001    0b11
+    "INX",          // 0xe8    0b111010    0b00
+    "SBC imm",      // 0xe9    0b111010    0b01
+    "NOP",          // 0xea    0b111010    0b10
+    "NOP",          // 0xeb    0b111010    0b11
+    "CPX abs",      // 0xec    0b111011    0b00
+    "SBC abs",      // 0xed    0b111011    0b01
+    "INC abs",      // 0xee    0b111011    0b10
+    "BBS6 zpg,rel", // 0xef    0b111011    0b11
+    "BEQ rel",      // 0xf0    0b111100    0b00
+    "SBC ind,Y",    // 0xf1    0b111100    0b01
+    "SBC (zpg)",    // 0xf2    0b111100    0b10
+    "NOP",          // 0xf3    0b111100    0b11
+    "NOP zpg,X",    // 0xf4    0b111101    0b00
+    "SBC zpg,X",    // 0xf5    0b111101    0b01
+    "INC zpg,X",    // 0xf6    0b111101    0b10
+    "SMB7 zpg",     // 0xf7    0b111101    0b11
+    "SED",          // 0xf8    0b111110    0b00
+    "SBC abs,Y",    // 0xf9    0b111110    0b01
+    "PLX",          // 0xfa    0b111110    0b10
+    "NOP",          // 0xfb    0b111110    0b11
+    "NOP abs",      // 0xfc    0b111111    0b00
+    "SBC abs,X",    // 0xfd    0b111111    0b01
+    "INC abs,X",    // 0xfe    0b111111    0b10
+    "BBS7 zpg,rel", // 0xff    0b111111    0b11
+];
+
+/// Base cycle counts for instructions.
+///
+/// The page-crossing penalty for the indexed reads and the penalty
+/// for the taken branches are not included, these are added when
+/// executing the instruction. The "undocumented" instructions are
+/// listed with their NMOS timings, and the instructions that jam the
+/// processor have no meaningful cycle count.
+const INSN_CYCLES: [u8; 256] = [
+    //  0x0 0x1 0x2 0x3 0x4 0x5 0x6 0x7 0x8 0x9 0xa 0xb 0xc 0xd 0xe 0xf
+    7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 0x00
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x10
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 0x20
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x30
+    6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 0x40
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x50
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 0x60
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x70
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0x80
+    2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 0x90
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0xa0
+    2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // 0xb0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xc0
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xd0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xe0
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xf0
+];
+
+/// Base cycle counts for the instructions of the WDC 65C02.
+///
+/// Same as for the NMOS 6502 with a few exceptions: JMP indirect takes
+/// an extra cycle, the shifts and rotations with the `abs,X` operand
+/// get the page-crossing penalty instead of taking it always, and the
+/// opcodes left undefined are NOPs of various lengths. ADC and SBC take
+/// an extra cycle in the decimal mode, and BBR/BBS take the penalty for
+/// the taken branches, that is added when executing them.
+const INSN_CYCLES_65C02: [u8; 256] = [
+    //  0x0 0x1 0x2 0x3 0x4 0x5 0x6 0x7 0x8 0x9 0xa 0xb 0xc 0xd 0xe 0xf
+    7, 6, 2, 1, 5, 3, 5, 5, 3, 2, 2, 1, 6, 4, 6, 5, // 0x00
+    2, 5, 5, 1, 5, 4, 6, 5, 2, 4, 2, 1, 6, 4, 6, 5, // 0x10
+    6, 6, 2, 1, 3, 3, 5, 5, 4, 2, 2, 1, 4, 4, 6, 5, // 0x20
+    2, 5, 5, 1, 4, 4, 6, 5, 2, 4, 2, 1, 4, 4, 6, 5, // 0x30
+    6, 6, 2, 1, 3, 3, 5, 5, 3, 2, 2, 1, 3, 4, 6, 5, // 0x40
+    2, 5, 5, 1, 4, 4, 6, 5, 2, 4, 3, 1, 8, 4, 6, 5, // 0x50
+    6, 6, 2, 1, 3, 3, 5, 5, 4, 2, 2, 1, 6, 4, 6, 5, // 0x60
+    2, 5, 5, 1, 4, 4, 6, 5, 2, 4, 4, 1, 6, 4, 6, 5, // 0x70
+    2, 6, 2, 1, 3, 3, 3, 5, 2, 2, 2, 1, 4, 4, 4, 5, // 0x80
+    2, 6, 5, 1, 4, 4, 4, 5, 2, 5, 2, 1, 4, 5, 5, 5, // 0x90
+    2, 6, 2, 1, 3, 3, 3, 5, 2, 2, 2, 1, 4, 4, 4, 5, // 0xa0
+    2, 5, 5, 1, 4, 4, 4, 5, 2, 4, 2, 1, 4, 4, 4, 5, // 0xb0
+    2, 6, 2, 1, 3, 3, 5, 5, 2, 2, 2, 3, 4, 4, 6, 5, // 0xc0
+    2, 5, 5, 1, 4, 4, 6, 5, 2, 4, 3, 3, 4, 4, 7, 5, // 0xd0
+    2, 6, 2, 1, 3, 3, 5, 5, 2, 2, 2, 1, 4, 4, 6, 5, // 0xe0
+    2, 5, 5, 1, 4, 4, 6, 5, 2, 4, 4, 1, 4, 4, 7, 5, // 0xf0
 ];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -278,6 +604,13 @@ pub enum AddressMode {
     Zeropage,
     ZeropageX,
     ZeropageY,
+    /// 65C02: `(zp)`, the pointer in the zero page is not indexed
+    ZeropageIndirect,
+    /// 65C02: `(abs,X)`, only for JMP
+    AbsoluteXindirect,
+    /// 65C02: `zp,rel`, the zero page location to test, and the branch
+    /// offset following it. Only for BBR and BBS
+    ZeropageRelative,
 }
 
 /// Instruction representation.
@@ -286,9 +619,19 @@ pub enum AddressMode {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Insn {
     ADC(AddressMode),
+    /// Undocumented: AND, then LSR A
+    ALR(AddressMode),
+    /// Undocumented: AND, then copy N to C
+    ANC(AddressMode),
     AND(AddressMode),
+    /// Undocumented: AND, then ROR A with peculiar flags
+    ARR(AddressMode),
     ASLA,
     ASL(AddressMode),
+    /// 65C02: branch if the bit of the zero page location is reset
+    BBR(u8, AddressMode),
+    /// 65C02: branch if the bit of the zero page location is set
+    BBS(u8, AddressMode),
     BCC(AddressMode),
     BCS(AddressMode),
     BEQ(AddressMode),
@@ -296,6 +639,8 @@ pub enum Insn {
     BMI(AddressMode),
     BNE(AddressMode),
     BPL(AddressMode),
+    /// 65C02: branch always
+    BRA(AddressMode),
     BRK,
     BVC(AddressMode),
     BVS(AddressMode),
@@ -306,51 +651,157 @@ pub enum Insn {
     CMP(AddressMode),
     CPX(AddressMode),
     CPY(AddressMode),
+    /// Undocumented: DEC, then CMP
+    DCP(AddressMode),
     DEC(AddressMode),
+    /// 65C02: DEC A
+    DECA,
     DEX,
     DEY,
     EOR(AddressMode),
     INC(AddressMode),
+    /// 65C02: INC A
+    INCA,
     INX,
     INY,
+    /// Undocumented: INC, then SBC
+    ISC(AddressMode),
     JAM,
     JMP(AddressMode),
     JSR(AddressMode),
+    /// Undocumented: LDA and LDX at once
+    LAX(AddressMode),
     LDA(AddressMode),
     LDX(AddressMode),
     LDY(AddressMode),
     LSRA,
     LSR(AddressMode),
     NOP,
+    /// Undocumented: NOP reading the operand
+    NOPM(AddressMode),
     ORA(AddressMode),
     PHA,
     PHP,
+    /// 65C02: push X
+    PHX,
+    /// 65C02: push Y
+    PHY,
     PLA,
     PLP,
+    /// 65C02: pull X
+    PLX,
+    /// 65C02: pull Y
+    PLY,
+    /// 65C02: reset the bit of the zero page location
+    RMB(u8, AddressMode),
+    /// Undocumented: ROL, then AND
+    RLA(AddressMode),
     ROLA,
     ROL(AddressMode),
     RORA,
     ROR(AddressMode),
+    /// Undocumented: ROR, then ADC
+    RRA(AddressMode),
     RTI,
     RTS,
+    /// Undocumented: store A AND X
+    SAX(AddressMode),
     SBC(AddressMode),
+    /// Undocumented: X = (A AND X) - operand, setting the flags as CMP
+    SBX(AddressMode),
     SEC,
     SED,
     SEI,
+    /// 65C02: set the bit of the zero page location
+    SMB(u8, AddressMode),
+    /// Undocumented: ASL, then ORA
+    SLO(AddressMode),
+    /// Undocumented: LSR, then EOR
+    SRE(AddressMode),
     STA(AddressMode),
+    /// 65C02: stop the processor until reset
+    STP,
     STX(AddressMode),
     STY(AddressMode),
+    /// 65C02: store zero
+    STZ(AddressMode),
     TAX,
     TAY,
+    /// 65C02: test and reset the bits set in A
+    TRB(AddressMode),
+    /// 65C02: test and set the bits set in A
+    TSB(AddressMode),
     TSX,
     TXA,
     TXS,
     TYA,
+    /// 65C02: wait for an interrupt
+    WAI,
 }
 
 impl Insn {
-    pub fn is_valid(&self) -> bool {
-        *self != Insn::JAM
+    pub const fn is_valid(&self) -> bool {
+        !matches!(self, Insn::JAM)
+    }
+
+    /// Addressing mode of the instruction, `None` for the instructions
+    /// without an operand.
+    pub const fn addr_mode(&self) -> Option<AddressMode> {
+        match *self {
+            Insn::ADC(addr_mode)
+            | Insn::ALR(addr_mode)
+            | Insn::ANC(addr_mode)
+            | Insn::AND(addr_mode)
+            | Insn::ARR(addr_mode)
+            | Insn::ASL(addr_mode)
+            | Insn::BBR(_, addr_mode)
+            | Insn::BBS(_, addr_mode)
+            | Insn::BCC(addr_mode)
+            | Insn::BCS(addr_mode)
+            | Insn::BEQ(addr_mode)
+            | Insn::BIT(addr_mode)
+            | Insn::BMI(addr_mode)
+            | Insn::BNE(addr_mode)
+            | Insn::BPL(addr_mode)
+            | Insn::BRA(addr_mode)
+            | Insn::BVC(addr_mode)
+            | Insn::BVS(addr_mode)
+            | Insn::CMP(addr_mode)
+            | Insn::CPX(addr_mode)
+            | Insn::CPY(addr_mode)
+            | Insn::DCP(addr_mode)
+            | Insn::DEC(addr_mode)
+            | Insn::EOR(addr_mode)
+            | Insn::INC(addr_mode)
+            | Insn::ISC(addr_mode)
+            | Insn::JMP(addr_mode)
+            | Insn::JSR(addr_mode)
+            | Insn::LAX(addr_mode)
+            | Insn::LDA(addr_mode)
+            | Insn::LDX(addr_mode)
+            | Insn::LDY(addr_mode)
+            | Insn::LSR(addr_mode)
+            | Insn::NOPM(addr_mode)
+            | Insn::ORA(addr_mode)
+            | Insn::RLA(addr_mode)
+            | Insn::RMB(_, addr_mode)
+            | Insn::ROL(addr_mode)
+            | Insn::ROR(addr_mode)
+            | Insn::RRA(addr_mode)
+            | Insn::SAX(addr_mode)
+            | Insn::SBC(addr_mode)
+            | Insn::SBX(addr_mode)
+            | Insn::SLO(addr_mode)
+            | Insn::SMB(_, addr_mode)
+            | Insn::SRE(addr_mode)
+            | Insn::STA(addr_mode)
+            | Insn::STX(addr_mode)
+            | Insn::STY(addr_mode)
+            | Insn::STZ(addr_mode)
+            | Insn::TRB(addr_mode)
+            | Insn::TSB(addr_mode) => Some(addr_mode),
+            _ => None,
+        }
     }
 }
 
@@ -692,7 +1143,93 @@ const INSN_BY_GROUP: [[[Insn; 8]; 8]; 4] = [
     ],
 ];
 
-pub fn decode_insn(opcode: u8) -> Insn {
+/// The "undocumented" instructions in the group 0b11 combine
+/// the operations of the groups 0b01 and 0b10 with the addressing
+/// modes of the group 0b01. The unstable ones jam.
+const UNDOCUMENTED_INSN_GROUP_0B11: [[Insn; 8]; 8] = [
+    [
+        Insn::SLO(AddressMode::Xindirect),
+        Insn::SLO(AddressMode::Zeropage),
+        Insn::ANC(AddressMode::Immediate),
+        Insn::SLO(AddressMode::Absolute),
+        Insn::SLO(AddressMode::IndirectY),
+        Insn::SLO(AddressMode::ZeropageX),
+        Insn::SLO(AddressMode::AbsoluteY),
+        Insn::SLO(AddressMode::AbsoluteX),
+    ],
+    [
+        Insn::RLA(AddressMode::Xindirect),
+        Insn::RLA(AddressMode::Zeropage),
+        Insn::ANC(AddressMode::Immediate),
+        Insn::RLA(AddressMode::Absolute),
+        Insn::RLA(AddressMode::IndirectY),
+        Insn::RLA(AddressMode::ZeropageX),
+        Insn::RLA(AddressMode::AbsoluteY),
+        Insn::RLA(AddressMode::AbsoluteX),
+    ],
+    [
+        Insn::SRE(AddressMode::Xindirect),
+        Insn::SRE(AddressMode::Zeropage),
+        Insn::ALR(AddressMode::Immediate),
+        Insn::SRE(AddressMode::Absolute),
+        Insn::SRE(AddressMode::IndirectY),
+        Insn::SRE(AddressMode::ZeropageX),
+        Insn::SRE(AddressMode::AbsoluteY),
+        Insn::SRE(AddressMode::AbsoluteX),
+    ],
+    [
+        Insn::RRA(AddressMode::Xindirect),
+        Insn::RRA(AddressMode::Zeropage),
+        Insn::ARR(AddressMode::Immediate),
+        Insn::RRA(AddressMode::Absolute),
+        Insn::RRA(AddressMode::IndirectY),
+        Insn::RRA(AddressMode::ZeropageX),
+        Insn::RRA(AddressMode::AbsoluteY),
+        Insn::RRA(AddressMode::AbsoluteX),
+    ],
+    [
+        Insn::SAX(AddressMode::Xindirect),
+        Insn::SAX(AddressMode::Zeropage),
+        Insn::JAM,
+        Insn::SAX(AddressMode::Absolute),
+        Insn::JAM,
+        Insn::SAX(AddressMode::ZeropageY),
+        Insn::JAM,
+        Insn::JAM,
+    ],
+    [
+        Insn::LAX(AddressMode::Xindirect),
+        Insn::LAX(AddressMode::Zeropage),
+        Insn::JAM,
+        Insn::LAX(AddressMode::Absolute),
+        Insn::LAX(AddressMode::IndirectY),
+        Insn::LAX(AddressMode::ZeropageY),
+        Insn::JAM,
+        Insn::LAX(AddressMode::AbsoluteY),
+    ],
+    [
+        Insn::DCP(AddressMode::Xindirect),
+        Insn::DCP(AddressMode::Zeropage),
+        Insn::SBX(AddressMode::Immediate),
+        Insn::DCP(AddressMode::Absolute),
+        Insn::DCP(AddressMode::IndirectY),
+        Insn::DCP(AddressMode::ZeropageX),
+        Insn::DCP(AddressMode::AbsoluteY),
+        Insn::DCP(AddressMode::AbsoluteX),
+    ],
+    [
+        Insn::ISC(AddressMode::Xindirect),
+        Insn::ISC(AddressMode::Zeropage),
+        Insn::SBC(AddressMode::Immediate),
+        Insn::ISC(AddressMode::Absolute),
+        Insn::ISC(AddressMode::IndirectY),
+        Insn::ISC(AddressMode::ZeropageX),
+        Insn::ISC(AddressMode::AbsoluteY),
+        Insn::ISC(AddressMode::AbsoluteX),
+    ],
+];
+
+pub const fn decode_insn(opcode: u8) -> Insn {
     let group = (opcode & 0b11) as usize;
     let two_octals = (opcode >> 2) as usize;
     let lo_octal = two_octals & 0b111;
@@ -701,6 +1238,94 @@ pub fn decode_insn(opcode: u8) -> Insn {
     INSN_BY_GROUP[group][hi_octal][lo_octal]
 }
 
+/// Decodes the opcode including the stable "undocumented" instructions
+/// of the NMOS 6502. The rest of the invalid opcodes jam.
+pub const fn decode_undocumented_insn(opcode: u8) -> Insn {
+    let insn = decode_insn(opcode);
+    if insn.is_valid() {
+        return insn;
+    }
+
+    let group = (opcode & 0b11) as usize;
+    if group == 0b11 {
+        let two_octals = (opcode >> 2) as usize;
+        let lo_octal = two_octals & 0b111;
+        let hi_octal = two_octals >> 3;
+
+        return UNDOCUMENTED_INSN_GROUP_0B11[hi_octal][lo_octal];
+    }
+
+    // The rest are NOPs of various lengths, and the opcodes that
+    // do jam the processor.
+    match opcode {
+        0x1a | 0x3a | 0x5a | 0x7a | 0xda | 0xfa => Insn::NOP,
+        0x80 | 0x82 | 0x89 | 0xc2 | 0xe2 => Insn::NOPM(AddressMode::Immediate),
+        0x04 | 0x44 | 0x64 => Insn::NOPM(AddressMode::Zeropage),
+        0x14 | 0x34 | 0x54 | 0x74 | 0xd4 | 0xf4 => Insn::NOPM(AddressMode::ZeropageX),
+        0x0c => Insn::NOPM(AddressMode::Absolute),
+        0x1c | 0x3c | 0x5c | 0x7c | 0xdc | 0xfc => Insn::NOPM(AddressMode::AbsoluteX),
+        _ => Insn::JAM,
+    }
+}
+
+/// Decodes the opcode for the WDC 65C02. The opcodes left undefined
+/// are NOPs of various lengths, none of them jams the processor.
+pub const fn decode_65c02_insn(opcode: u8) -> Insn {
+    let insn = decode_insn(opcode);
+    if insn.is_valid() {
+        return insn;
+    }
+
+    // The bit manipulation instructions encode the bit number
+    // in the high nibble
+    let bit = (opcode >> 4) & 0b111;
+    match opcode & 0x8f {
+        0x07 => return Insn::RMB(bit, AddressMode::Zeropage),
+        0x87 => return Insn::SMB(bit, AddressMode::Zeropage),
+        0x0f => return Insn::BBR(bit, AddressMode::ZeropageRelative),
+        0x8f => return Insn::BBS(bit, AddressMode::ZeropageRelative),
+        _ => {}
+    }
+
+    match opcode {
+        0xcb => Insn::WAI,
+        0xdb => Insn::STP,
+        0x12 => Insn::ORA(AddressMode::ZeropageIndirect),
+        0x32 => Insn::AND(AddressMode::ZeropageIndirect),
+        0x52 => Insn::EOR(AddressMode::ZeropageIndirect),
+        0x72 => Insn::ADC(AddressMode::ZeropageIndirect),
+        0x92 => Insn::STA(AddressMode::ZeropageIndirect),
+        0xb2 => Insn::LDA(AddressMode::ZeropageIndirect),
+        0xd2 => Insn::CMP(AddressMode::ZeropageIndirect),
+        0xf2 => Insn::SBC(AddressMode::ZeropageIndirect),
+        0x04 => Insn::TSB(AddressMode::Zeropage),
+        0x0c => Insn::TSB(AddressMode::Absolute),
+        0x14 => Insn::TRB(AddressMode::Zeropage),
+        0x1c => Insn::TRB(AddressMode::Absolute),
+        0x1a => Insn::INCA,
+        0x3a => Insn::DECA,
+        0x34 => Insn::BIT(AddressMode::ZeropageX),
+        0x3c => Insn::BIT(AddressMode::AbsoluteX),
+        0x89 => Insn::BIT(AddressMode::Immediate),
+        0x5a => Insn::PHY,
+        0x7a => Insn::PLY,
+        0xda => Insn::PHX,
+        0xfa => Insn::PLX,
+        0x64 => Insn::STZ(AddressMode::Zeropage),
+        0x74 => Insn::STZ(AddressMode::ZeropageX),
+        0x9c => Insn::STZ(AddressMode::Absolute),
+        0x9e => Insn::STZ(AddressMode::AbsoluteX),
+        0x7c => Insn::JMP(AddressMode::AbsoluteXindirect),
+        0x80 => Insn::BRA(AddressMode::Relative),
+        0x02 | 0x22 | 0x42 | 0x62 | 0x82 | 0xc2 | 0xe2 => Insn::NOPM(AddressMode::Immediate),
+        0x44 => Insn::NOPM(AddressMode::Zeropage),
+        0x54 | 0xd4 | 0xf4 => Insn::NOPM(AddressMode::ZeropageX),
+        0x5c | 0xdc | 0xfc => Insn::NOPM(AddressMode::Absolute),
+        // Single-byte, single-cycle NOPs
+        _ => Insn::NOP,
+    }
+}
+
 pub fn encode_insn(insn: Insn) -> u8 {
     match insn {
         Insn::ADC(AddressMode::Absolute) => 0x6d,
@@ -711,6 +1336,9 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::ADC(AddressMode::Xindirect) => 0x61,
         Insn::ADC(AddressMode::Zeropage) => 0x65,
         Insn::ADC(AddressMode::ZeropageX) => 0x75,
+        Insn::ADC(AddressMode::ZeropageIndirect) => 0x72,
+        Insn::ALR(AddressMode::Immediate) => 0x4b,
+        Insn::ANC(AddressMode::Immediate) => 0x0b,
         Insn::AND(AddressMode::Absolute) => 0x2d,
         Insn::AND(AddressMode::AbsoluteX) => 0x3d,
         Insn::AND(AddressMode::AbsoluteY) => 0x39,
@@ -719,19 +1347,27 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::AND(AddressMode::Xindirect) => 0x21,
         Insn::AND(AddressMode::Zeropage) => 0x25,
         Insn::AND(AddressMode::ZeropageX) => 0x35,
+        Insn::AND(AddressMode::ZeropageIndirect) => 0x32,
+        Insn::ARR(AddressMode::Immediate) => 0x6b,
         Insn::ASL(AddressMode::Absolute) => 0x0e,
         Insn::ASL(AddressMode::AbsoluteX) => 0x1e,
         Insn::ASLA => 0x0a,
         Insn::ASL(AddressMode::Zeropage) => 0x06,
         Insn::ASL(AddressMode::ZeropageX) => 0x16,
+        Insn::BBR(bit, AddressMode::ZeropageRelative) if bit < 8 => 0x0f | (bit << 4),
+        Insn::BBS(bit, AddressMode::ZeropageRelative) if bit < 8 => 0x8f | (bit << 4),
         Insn::BCC(AddressMode::Relative) => 0x90,
         Insn::BCS(AddressMode::Relative) => 0xb0,
         Insn::BEQ(AddressMode::Relative) => 0xf0,
         Insn::BIT(AddressMode::Absolute) => 0x2c,
+        Insn::BIT(AddressMode::AbsoluteX) => 0x3c,
+        Insn::BIT(AddressMode::Immediate) => 0x89,
         Insn::BIT(AddressMode::Zeropage) => 0x24,
+        Insn::BIT(AddressMode::ZeropageX) => 0x34,
         Insn::BMI(AddressMode::Relative) => 0x30,
         Insn::BNE(AddressMode::Relative) => 0xd0,
         Insn::BPL(AddressMode::Relative) => 0x10,
+        Insn::BRA(AddressMode::Relative) => 0x80,
         Insn::BRK => 0x00,
         Insn::BVC(AddressMode::Relative) => 0x50,
         Insn::BVS(AddressMode::Relative) => 0x70,
@@ -747,16 +1383,25 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::CMP(AddressMode::Xindirect) => 0xc1,
         Insn::CMP(AddressMode::Zeropage) => 0xc5,
         Insn::CMP(AddressMode::ZeropageX) => 0xd5,
+        Insn::CMP(AddressMode::ZeropageIndirect) => 0xd2,
         Insn::CPX(AddressMode::Absolute) => 0xec,
         Insn::CPX(AddressMode::Immediate) => 0xe0,
         Insn::CPX(AddressMode::Zeropage) => 0xe4,
         Insn::CPY(AddressMode::Absolute) => 0xcc,
         Insn::CPY(AddressMode::Immediate) => 0xc0,
         Insn::CPY(AddressMode::Zeropage) => 0xc4,
+        Insn::DCP(AddressMode::Absolute) => 0xcf,
+        Insn::DCP(AddressMode::AbsoluteX) => 0xdf,
+        Insn::DCP(AddressMode::AbsoluteY) => 0xdb,
+        Insn::DCP(AddressMode::IndirectY) => 0xd3,
+        Insn::DCP(AddressMode::Xindirect) => 0xc3,
+        Insn::DCP(AddressMode::Zeropage) => 0xc7,
+        Insn::DCP(AddressMode::ZeropageX) => 0xd7,
         Insn::DEC(AddressMode::Absolute) => 0xce,
         Insn::DEC(AddressMode::AbsoluteX) => 0xde,
         Insn::DEC(AddressMode::Zeropage) => 0xc6,
         Insn::DEC(AddressMode::ZeropageX) => 0xd6,
+        Insn::DECA => 0x3a,
         Insn::DEX => 0xca,
         Insn::DEY => 0x88,
         Insn::EOR(AddressMode::Absolute) => 0x4d,
@@ -767,15 +1412,31 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::EOR(AddressMode::Xindirect) => 0x41,
         Insn::EOR(AddressMode::Zeropage) => 0x45,
         Insn::EOR(AddressMode::ZeropageX) => 0x55,
+        Insn::EOR(AddressMode::ZeropageIndirect) => 0x52,
         Insn::INC(AddressMode::Absolute) => 0xee,
         Insn::INC(AddressMode::AbsoluteX) => 0xfe,
         Insn::INC(AddressMode::Zeropage) => 0xe6,
         Insn::INC(AddressMode::ZeropageX) => 0xf6,
+        Insn::INCA => 0x1a,
         Insn::INX => 0xe8,
         Insn::INY => 0xc8,
+        Insn::ISC(AddressMode::Absolute) => 0xef,
+        Insn::ISC(AddressMode::AbsoluteX) => 0xff,
+        Insn::ISC(AddressMode::AbsoluteY) => 0xfb,
+        Insn::ISC(AddressMode::IndirectY) => 0xf3,
+        Insn::ISC(AddressMode::Xindirect) => 0xe3,
+        Insn::ISC(AddressMode::Zeropage) => 0xe7,
+        Insn::ISC(AddressMode::ZeropageX) => 0xf7,
         Insn::JMP(AddressMode::Absolute) => 0x4c,
         Insn::JMP(AddressMode::Indirect) => 0x6c,
+        Insn::JMP(AddressMode::AbsoluteXindirect) => 0x7c,
         Insn::JSR(AddressMode::Absolute) => 0x20,
+        Insn::LAX(AddressMode::Absolute) => 0xaf,
+        Insn::LAX(AddressMode::AbsoluteY) => 0xbf,
+        Insn::LAX(AddressMode::IndirectY) => 0xb3,
+        Insn::LAX(AddressMode::Xindirect) => 0xa3,
+        Insn::LAX(AddressMode::Zeropage) => 0xa7,
+        Insn::LAX(AddressMode::ZeropageY) => 0xb7,
         Insn::LDA(AddressMode::Absolute) => 0xad, // got a test
         Insn::LDA(AddressMode::AbsoluteX) => 0xbd, // got a test
         Insn::LDA(AddressMode::AbsoluteY) => 0xb9, // got a test
@@ -784,6 +1445,7 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::LDA(AddressMode::Xindirect) => 0xa1, // got a test
         Insn::LDA(AddressMode::Zeropage) => 0xa5, // got a test
         Insn::LDA(AddressMode::ZeropageX) => 0xb5, // got a test
+        Insn::LDA(AddressMode::ZeropageIndirect) => 0xb2,
         Insn::LDX(AddressMode::Absolute) => 0xae, // got a test
         Insn::LDX(AddressMode::AbsoluteY) => 0xbe, // got a test
         Insn::LDX(AddressMode::Immediate) => 0xa2, // got a test
@@ -800,6 +1462,11 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::LSR(AddressMode::Zeropage) => 0x46,
         Insn::LSR(AddressMode::ZeropageX) => 0x56,
         Insn::NOP => 0xea,
+        Insn::NOPM(AddressMode::Absolute) => 0x0c,
+        Insn::NOPM(AddressMode::AbsoluteX) => 0x1c,
+        Insn::NOPM(AddressMode::Immediate) => 0x80,
+        Insn::NOPM(AddressMode::Zeropage) => 0x04,
+        Insn::NOPM(AddressMode::ZeropageX) => 0x14,
         Insn::ORA(AddressMode::Absolute) => 0x0d,
         Insn::ORA(AddressMode::AbsoluteX) => 0x1d,
         Insn::ORA(AddressMode::AbsoluteY) => 0x19,
@@ -808,10 +1475,23 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::ORA(AddressMode::Xindirect) => 0x01,
         Insn::ORA(AddressMode::Zeropage) => 0x05,
         Insn::ORA(AddressMode::ZeropageX) => 0x15,
+        Insn::ORA(AddressMode::ZeropageIndirect) => 0x12,
         Insn::PHA => 0x48,
         Insn::PHP => 0x08,
+        Insn::PHX => 0xda,
+        Insn::PHY => 0x5a,
         Insn::PLA => 0x68,
         Insn::PLP => 0x28,
+        Insn::PLX => 0xfa,
+        Insn::PLY => 0x7a,
+        Insn::RLA(AddressMode::Absolute) => 0x2f,
+        Insn::RLA(AddressMode::AbsoluteX) => 0x3f,
+        Insn::RLA(AddressMode::AbsoluteY) => 0x3b,
+        Insn::RLA(AddressMode::IndirectY) => 0x33,
+        Insn::RLA(AddressMode::Xindirect) => 0x23,
+        Insn::RLA(AddressMode::Zeropage) => 0x27,
+        Insn::RLA(AddressMode::ZeropageX) => 0x37,
+        Insn::RMB(bit, AddressMode::Zeropage) if bit < 8 => 0x07 | (bit << 4),
         Insn::ROL(AddressMode::Absolute) => 0x2e,
         Insn::ROL(AddressMode::AbsoluteX) => 0x3e,
         Insn::ROLA => 0x2a,
@@ -822,8 +1502,19 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::RORA => 0x6a,
         Insn::ROR(AddressMode::Zeropage) => 0x66,
         Insn::ROR(AddressMode::ZeropageX) => 0x76,
+        Insn::RRA(AddressMode::Absolute) => 0x6f,
+        Insn::RRA(AddressMode::AbsoluteX) => 0x7f,
+        Insn::RRA(AddressMode::AbsoluteY) => 0x7b,
+        Insn::RRA(AddressMode::IndirectY) => 0x73,
+        Insn::RRA(AddressMode::Xindirect) => 0x63,
+        Insn::RRA(AddressMode::Zeropage) => 0x67,
+        Insn::RRA(AddressMode::ZeropageX) => 0x77,
         Insn::RTI => 0x40,
         Insn::RTS => 0x60,
+        Insn::SAX(AddressMode::Absolute) => 0x8f,
+        Insn::SAX(AddressMode::Xindirect) => 0x83,
+        Insn::SAX(AddressMode::Zeropage) => 0x87,
+        Insn::SAX(AddressMode::ZeropageY) => 0x97,
         Insn::SBC(AddressMode::Absolute) => 0xed,
         Insn::SBC(AddressMode::AbsoluteX) => 0xfd,
         Insn::SBC(AddressMode::AbsoluteY) => 0xf9,
@@ -832,9 +1523,26 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::SBC(AddressMode::Xindirect) => 0xe1,
         Insn::SBC(AddressMode::Zeropage) => 0xe5,
         Insn::SBC(AddressMode::ZeropageX) => 0xf5,
+        Insn::SBC(AddressMode::ZeropageIndirect) => 0xf2,
+        Insn::SBX(AddressMode::Immediate) => 0xcb,
         Insn::SEC => 0x38,
         Insn::SED => 0xf8,
         Insn::SEI => 0x78,
+        Insn::SLO(AddressMode::Absolute) => 0x0f,
+        Insn::SLO(AddressMode::AbsoluteX) => 0x1f,
+        Insn::SLO(AddressMode::AbsoluteY) => 0x1b,
+        Insn::SLO(AddressMode::IndirectY) => 0x13,
+        Insn::SLO(AddressMode::Xindirect) => 0x03,
+        Insn::SLO(AddressMode::Zeropage) => 0x07,
+        Insn::SLO(AddressMode::ZeropageX) => 0x17,
+        Insn::SMB(bit, AddressMode::Zeropage) if bit < 8 => 0x87 | (bit << 4),
+        Insn::SRE(AddressMode::Absolute) => 0x4f,
+        Insn::SRE(AddressMode::AbsoluteX) => 0x5f,
+        Insn::SRE(AddressMode::AbsoluteY) => 0x5b,
+        Insn::SRE(AddressMode::IndirectY) => 0x53,
+        Insn::SRE(AddressMode::Xindirect) => 0x43,
+        Insn::SRE(AddressMode::Zeropage) => 0x47,
+        Insn::SRE(AddressMode::ZeropageX) => 0x57,
         Insn::STA(AddressMode::Absolute) => 0x8d,
         Insn::STA(AddressMode::AbsoluteX) => 0x9d,
         Insn::STA(AddressMode::AbsoluteY) => 0x99,
@@ -842,18 +1550,29 @@ pub fn encode_insn(insn: Insn) -> u8 {
         Insn::STA(AddressMode::Xindirect) => 0x81,
         Insn::STA(AddressMode::Zeropage) => 0x85,
         Insn::STA(AddressMode::ZeropageX) => 0x95,
+        Insn::STA(AddressMode::ZeropageIndirect) => 0x92,
         Insn::STX(AddressMode::Absolute) => 0x8e,
         Insn::STX(AddressMode::Zeropage) => 0x86,
         Insn::STX(AddressMode::ZeropageY) => 0x96,
         Insn::STY(AddressMode::Absolute) => 0x8c,
         Insn::STY(AddressMode::Zeropage) => 0x84,
         Insn::STY(AddressMode::ZeropageX) => 0x94,
+        Insn::STZ(AddressMode::Absolute) => 0x9c,
+        Insn::STZ(AddressMode::AbsoluteX) => 0x9e,
+        Insn::STZ(AddressMode::Zeropage) => 0x64,
+        Insn::STZ(AddressMode::ZeropageX) => 0x74,
         Insn::TAX => 0xaa,
         Insn::TAY => 0xa8,
+        Insn::TRB(AddressMode::Absolute) => 0x1c,
+        Insn::TRB(AddressMode::Zeropage) => 0x14,
+        Insn::TSB(AddressMode::Absolute) => 0x0c,
+        Insn::TSB(AddressMode::Zeropage) => 0x04,
         Insn::TSX => 0xba,
         Insn::TXA => 0x8a,
         Insn::TXS => 0x9a,
         Insn::TYA => 0x98,
+        Insn::STP => 0xdb,
+        Insn::WAI => 0xcb,
         _ => 0xff,
     }
 }
@@ -861,3 +1580,52 @@ pub fn encode_insn(insn: Insn) -> u8 {
 pub fn get_opcode_string(opcode: u8) -> &'static str {
     INSN_STR[opcode as usize]
 }
+
+/// Name of the opcode as the processor decodes it: the 65C02 has its own
+/// names, and the undocumented instructions not allowed are named JAM.
+pub fn get_opcode_string_for(
+    variant: CpuVariant,
+    undocumented: UndocumentedOpcodes,
+    opcode: u8,
+) -> &'static str {
+    let insn = match (variant, undocumented) {
+        (CpuVariant::Wdc65C02, _) => return INSN_STR_65C02[opcode as usize],
+        (_, UndocumentedOpcodes::Allow) => decode_undocumented_insn(opcode),
+        (_, UndocumentedOpcodes::Disallow) => decode_insn(opcode),
+    };
+    if !insn.is_valid() {
+        return "JAM";
+    }
+
+    INSN_STR[opcode as usize]
+}
+
+/// Base number of cycles the instruction takes, not counting
+/// the page-crossing and the branch-taken penalties.
+pub const fn get_opcode_cycles(opcode: u8) -> u8 {
+    INSN_CYCLES[opcode as usize]
+}
+
+/// Number of the bytes following the opcode of the NMOS 6502, as implied
+/// by the addressing mode of its column. BRK is followed by the break mark,
+/// and the opcodes halting the processor by nothing.
+pub fn get_operand_bytes(opcode: u8) -> u8 {
+    let column = (opcode >> 2) & 0b111;
+    match (column, opcode & 0b11) {
+        (3 | 7, _) | (6, 0b01 | 0b11) => 2,
+        (0, 0b00) => match opcode {
+            0x20 => 2,
+            0x40 | 0x60 => 0,
+            _ => 1,
+        },
+        (0, 0b10) if opcode < 0x80 => 0,
+        (2 | 6, 0b00 | 0b10) | (4, 0b10) => 0,
+        _ => 1,
+    }
+}
+
+/// Base number of cycles the WDC 65C02 instruction takes, not counting
+/// the page-crossing, the branch-taken and the decimal mode penalties.
+pub const fn get_65c02_opcode_cycles(opcode: u8) -> u8 {
+    INSN_CYCLES_65C02[opcode as usize]
+}
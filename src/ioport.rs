@@ -0,0 +1,116 @@
+//! On-chip I/O port of the MOS 6510
+//!
+//! The data direction register is at $0000, and the data register
+//! is at $0001. Only six lines (P0-P5) are bonded out, the bits 6 and 7
+//! configured as inputs are floating and read back the last value driven
+//! on them (the charge fading away after a while is not emulated).
+
+use core::fmt::Debug;
+
+/// Address of the data direction register, a set bit makes the line an output
+pub const IO_PORT_DDR: u16 = 0x0000;
+
+/// Address of the data register
+pub const IO_PORT_DATA: u16 = 0x0001;
+
+/// The lines bonded out to the pins
+const PINS_MASK: u8 = 0b0011_1111;
+
+/// MOS 6510 I/O port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoPort {
+    /// Data direction register
+    ddr: u8,
+    /// Output latch, the value written to the data register
+    latch: u8,
+    /// Levels driven on the pins from the outside
+    input_pins: u8,
+    /// Last values driven on the floating bits 6 and 7
+    floating: u8,
+}
+
+impl Default for IoPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoPort {
+    /// All lines are inputs, pulled up unless driven
+    /// from the outside.
+    pub fn new() -> Self {
+        Self {
+            ddr: 0,
+            latch: 0,
+            input_pins: PINS_MASK,
+            floating: 0,
+        }
+    }
+
+    /// Reset makes all lines inputs and clears the output latch.
+    /// The input pins are driven from the outside, and are left as is.
+    pub fn reset(&mut self) {
+        self.ddr = 0;
+        self.latch = 0;
+    }
+
+    pub fn ddr(&self) -> u8 {
+        self.ddr
+    }
+
+    pub fn latch(&self) -> u8 {
+        self.latch
+    }
+
+    pub fn input_pins(&self) -> u8 {
+        self.input_pins
+    }
+
+    /// Sets the levels driven on the pins configured as inputs.
+    /// Only the bits 0 to 5 are meaningful.
+    pub fn set_input_pins(&mut self, pins: u8) {
+        self.input_pins = pins & PINS_MASK;
+    }
+
+    /// Levels on the pins as the outside logic sees them: the output latch
+    /// for the outputs, and the input levels for the inputs.
+    pub fn pins(&self) -> u8 {
+        ((self.latch & self.ddr) | (self.input_pins & !self.ddr)) & PINS_MASK
+    }
+
+    /// The registers and the pin levels, for the snapshots
+    pub(crate) fn to_bytes(self) -> [u8; 4] {
+        [self.ddr, self.latch, self.input_pins, self.floating]
+    }
+
+    pub(crate) fn from_bytes(bytes: [u8; 4]) -> Self {
+        let [ddr, latch, input_pins, floating] = bytes;
+        Self {
+            ddr,
+            latch,
+            input_pins: input_pins & PINS_MASK,
+            floating,
+        }
+    }
+
+    pub(crate) fn read(&self, addr: u16) -> u8 {
+        if addr == IO_PORT_DDR {
+            return self.ddr;
+        }
+
+        let floating = (self.latch & self.ddr) | (self.floating & !self.ddr);
+        self.pins() | (floating & !PINS_MASK)
+    }
+
+    pub(crate) fn write(&mut self, addr: u16, value: u8) {
+        if addr == IO_PORT_DDR {
+            self.ddr = value;
+        } else {
+            self.latch = value;
+        }
+
+        // The floating bits configured as outputs are driven from the latch
+        let driven = self.ddr & !PINS_MASK;
+        self.floating = (self.floating & !driven) | (self.latch & driven);
+    }
+}
@@ -4,10 +4,16 @@
 
 mod bcd;
 mod insns;
+mod ioport;
 mod regfile;
+mod snapshot;
 mod tests;
+mod undo;
 mod yamos6502;
 
 pub use crate::insns::*;
+pub use crate::ioport::*;
 pub use crate::regfile::*;
+pub use crate::snapshot::*;
+pub use crate::undo::UndoHistory;
 pub use crate::yamos6502::*;
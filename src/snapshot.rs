@@ -0,0 +1,210 @@
+//! Snapshots of the machine state
+//!
+//! The processor state is saved into a fixed-size, versioned byte format:
+//! the magic "Y652", the version as a little-endian `u16`, and the state,
+//! all multi-byte values being little-endian. The enums are saved as their
+//! codes, the order of the variants in `Coded::ALL`, so the format does not
+//! change as the declarations move around.
+//!
+//! The machine snapshot is the processor snapshot followed by the length
+//! of the memory contents as a little-endian `u32` and the contents saved
+//! by the memory itself (`SnapshotMemory`).
+
+use core::fmt::Debug;
+
+use crate::Memory;
+
+/// Tells the snapshot apart from the other data
+const SNAPSHOT_MAGIC: [u8; 4] = *b"Y652";
+
+/// Bumped on every change to the format
+pub const SNAPSHOT_VERSION: u16 = 1;
+
+/// Size of the processor snapshot, including the magic and the version
+pub const SNAPSHOT_SIZE: usize = 63;
+
+/// Snapshot errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// Not a snapshot
+    BadMagic,
+    /// Saved in another version of the format
+    UnsupportedVersion(u16),
+    /// Ends before all of the state is read
+    Truncated,
+    /// The buffer is too small to save the snapshot into
+    BufferTooSmall,
+    /// The state read is not valid
+    Invalid,
+}
+
+impl core::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// The memory that can save and restore its contents
+pub trait SnapshotMemory: Memory {
+    /// Number of the bytes `save_contents` writes
+    fn contents_len(&self) -> usize;
+    /// Saves the contents into the buffer of `contents_len` bytes
+    fn save_contents(&self, out: &mut [u8]);
+    /// Restores the contents saved by `save_contents`, leaving them as
+    /// they are if the bytes are not valid
+    fn restore_contents(&mut self, bytes: &[u8]) -> Result<(), SnapshotError>;
+}
+
+/// Processor state saved in the stable byte format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    bytes: [u8; SNAPSHOT_SIZE],
+}
+
+impl Snapshot {
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes: [0; SNAPSHOT_SIZE],
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Checks the magic and the version, the rest of the bytes are checked
+    /// when the snapshot is restored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < SNAPSHOT_MAGIC.len() + 2 {
+            return Err(SnapshotError::Truncated);
+        }
+        if bytes[..SNAPSHOT_MAGIC.len()] != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let bytes = bytes.get(..SNAPSHOT_SIZE).ok_or(SnapshotError::Truncated)?;
+
+        let mut snapshot = Self::new();
+        snapshot.bytes.copy_from_slice(bytes);
+
+        Ok(snapshot)
+    }
+
+    pub(crate) fn writer(&mut self) -> SnapshotWriter<'_> {
+        let mut writer = SnapshotWriter {
+            bytes: &mut self.bytes,
+            pos: 0,
+        };
+        writer.bytes(&SNAPSHOT_MAGIC);
+        writer.u16(SNAPSHOT_VERSION);
+        writer
+    }
+
+    pub(crate) fn reader(&self) -> SnapshotReader<'_> {
+        SnapshotReader {
+            bytes: &self.bytes,
+            pos: SNAPSHOT_MAGIC.len() + 2,
+        }
+    }
+}
+
+/// The enums saved as their codes
+pub(crate) trait Coded: Copy + PartialEq + 'static {
+    /// The variants, each code is the index
+    const ALL: &'static [Self];
+}
+
+pub(crate) struct SnapshotWriter<'a> {
+    bytes: &'a mut [u8],
+    pos: usize,
+}
+
+impl SnapshotWriter<'_> {
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn code<T: Coded>(&mut self, value: T) {
+        let code = T::ALL.iter().position(|&v| v == value).unwrap_or_default();
+        self.u8(code as u8);
+    }
+
+    /// All of the snapshot has been written
+    pub(crate) fn finish(self) {
+        debug_assert!(self.pos == self.bytes.len());
+    }
+}
+
+pub(crate) struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl SnapshotReader<'_> {
+    pub(crate) fn bytes<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(
+            self.bytes
+                .get(self.pos..self.pos + N)
+                .ok_or(SnapshotError::Truncated)?,
+        );
+        self.pos += N;
+
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::Invalid),
+        }
+    }
+
+    pub(crate) fn code<T: Coded>(&mut self) -> Result<T, SnapshotError> {
+        T::ALL
+            .get(self.u8()? as usize)
+            .copied()
+            .ok_or(SnapshotError::Invalid)
+    }
+}
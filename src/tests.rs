@@ -1,11 +1,14 @@
 #![cfg(test)]
 
-use crate::bcd::bcd_to_u8;
-use crate::bcd::u8_to_bcd;
+use crate::bcd::cmos_add;
+use crate::bcd::cmos_sub;
+use crate::bcd::nmos_add;
+use crate::bcd::nmos_sub;
+use crate::bcd::BcdOutcome;
 use crate::*;
 
 struct TestMemory {
-    bytes: [u8; u16::MAX as usize],
+    bytes: [u8; MAX_MEMORY_SIZE],
 }
 
 impl TestMemory {
@@ -31,12 +34,13 @@ impl TestMemory {
 impl Default for TestMemory {
     fn default() -> Self {
         Self {
-            bytes: [0x55; u16::MAX as usize],
+            bytes: [0x55; MAX_MEMORY_SIZE],
         }
     }
 }
 
 impl Memory for TestMemory {
+    #[allow(clippy::unit_arg)]
     fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
         Ok(self.bytes[addr as usize] = value)
     }
@@ -44,6 +48,88 @@ impl Memory for TestMemory {
     fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
         Ok(self.bytes[addr as usize])
     }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.bytes[addr as usize])
+    }
+}
+
+impl SnapshotMemory for TestMemory {
+    fn contents_len(&self) -> usize {
+        MAX_MEMORY_SIZE
+    }
+
+    fn save_contents(&self, out: &mut [u8]) {
+        out.copy_from_slice(&self.bytes);
+    }
+
+    fn restore_contents(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        if bytes.len() != MAX_MEMORY_SIZE {
+            return Err(SnapshotError::Invalid);
+        }
+        self.bytes.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Memory recording the bus accesses, the written value is `None` for reads.
+#[derive(Default)]
+struct BusLogMemory {
+    memory: TestMemory,
+    log: [(u16, Option<u8>); 16],
+    kinds: [Option<AccessKind>; 16],
+    len: usize,
+}
+
+impl BusLogMemory {
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn accesses(&self) -> &[(u16, Option<u8>)] {
+        &self.log[..self.len]
+    }
+
+    fn kinds(&self) -> &[Option<AccessKind>] {
+        &self.kinds[..self.len]
+    }
+}
+
+impl Memory for BusLogMemory {
+    fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+        self.log[self.len] = (addr, Some(value));
+        self.len += 1;
+        Memory::write(&mut self.memory, addr, value)
+    }
+
+    fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
+        self.log[self.len] = (addr, None);
+        self.len += 1;
+        Memory::read(&mut self.memory, addr)
+    }
+
+    fn read_access(&mut self, addr: u16, kind: AccessKind) -> Result<u8, crate::MemoryError> {
+        self.kinds[self.len] = Some(kind);
+        self.read(addr)
+    }
+
+    fn write_access(
+        &mut self,
+        addr: u16,
+        value: u8,
+        kind: AccessKind,
+    ) -> Result<(), crate::MemoryError> {
+        self.kinds[self.len] = Some(kind);
+        self.write(addr, value)
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        self.memory.peek(addr)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+        self.memory.poke(addr, value)
+    }
 }
 
 const TEST_START: u16 = 0x0200;
@@ -393,6 +479,263 @@ fn test_alu() {
     assert!(mos6502.registers().a() == 0x89);
 }
 
+#[test]
+fn test_cycles() {
+    let mut memory = TestMemory::default();
+
+    memory.write_u16(RESET_VECTOR, TEST_START);
+
+    let program = [
+        encode_insn(Insn::LDX(AddressMode::Immediate)),
+        0x10,
+        // No page crossing
+        encode_insn(Insn::LDA(AddressMode::AbsoluteX)),
+        0x00,
+        (ABSOLUTE_START >> 8) as u8,
+        // Page crossing
+        encode_insn(Insn::LDA(AddressMode::AbsoluteX)),
+        0xf8,
+        (ABSOLUTE_START >> 8) as u8,
+        // Stores take the same number of cycles either way
+        encode_insn(Insn::STA(AddressMode::AbsoluteX)),
+        0xf8,
+        (ABSOLUTE_START >> 8) as u8,
+        encode_insn(Insn::INC(AddressMode::AbsoluteX)),
+        0x00,
+        (ABSOLUTE_START >> 8) as u8,
+        // Branch not taken
+        encode_insn(Insn::BEQ(AddressMode::Relative)),
+        0x00,
+        // Branch taken within the page
+        encode_insn(Insn::BCC(AddressMode::Relative)),
+        0x00,
+        // Branch taken to another page
+        encode_insn(Insn::BCC(AddressMode::Relative)),
+        0x80,
+    ];
+    memory.write(TEST_START, &program);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.set_reset_pending();
+
+    let expected = [
+        (Insn::LDX(AddressMode::Immediate), 2 + RESET_CYCLES as u64),
+        (Insn::LDA(AddressMode::AbsoluteX), 4),
+        (Insn::LDA(AddressMode::AbsoluteX), 5),
+        (Insn::STA(AddressMode::AbsoluteX), 5),
+        (Insn::INC(AddressMode::AbsoluteX), 7),
+        (Insn::BEQ(AddressMode::Relative), 2),
+        (Insn::BCC(AddressMode::Relative), 3),
+        (Insn::BCC(AddressMode::Relative), 4),
+    ];
+    let mut total = 0;
+    for (insn, cycles) in expected {
+        assert!(mos6502.run().unwrap() == RunExit::Executed(insn));
+        assert!(mos6502.last_run_cycles() == cycles);
+        total += cycles;
+    }
+    assert!(mos6502.cycles() == total);
+    assert!(mos6502.registers().pc() == TEST_START + program.len() as u16 - 0x80);
+}
+
+#[test]
+fn test_bus_cycle_stepped() {
+    let mut memory = BusLogMemory::default();
+
+    memory.memory.write_u8(0x1300, 0x7f);
+
+    let program = [
+        encode_insn(Insn::LDX(AddressMode::Immediate)),
+        0x20,
+        encode_insn(Insn::LDA(AddressMode::AbsoluteX)),
+        0xe0,
+        0x12,
+        encode_insn(Insn::INC(AddressMode::AbsoluteX)),
+        0xe0,
+        0x12,
+        encode_insn(Insn::JSR(AddressMode::Absolute)),
+        0x00,
+        0x03,
+    ];
+    memory.memory.write(TEST_START, &program);
+    memory.memory.write(
+        0x0300,
+        &[
+            encode_insn(Insn::PHA),
+            encode_insn(Insn::PLA),
+            encode_insn(Insn::RTS),
+        ],
+    );
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+
+    let expected: [&[(u16, Option<u8>)]; 7] = [
+        // LDX #$20
+        &[(0x0200, None), (0x0201, None)],
+        // LDA $12e0,X: the first read is from the wrong page
+        &[
+            (0x0202, None),
+            (0x0203, None),
+            (0x0204, None),
+            (0x1200, None),
+            (0x1300, None),
+        ],
+        // INC $12e0,X: the old value is written back first
+        &[
+            (0x0205, None),
+            (0x0206, None),
+            (0x0207, None),
+            (0x1200, None),
+            (0x1300, None),
+            (0x1300, Some(0x7f)),
+            (0x1300, Some(0x80)),
+        ],
+        // JSR $0300
+        &[
+            (0x0208, None),
+            (0x0209, None),
+            (0x01fd, None),
+            (0x01fd, Some(0x02)),
+            (0x01fc, Some(0x0a)),
+            (0x020a, None),
+        ],
+        // PHA
+        &[(0x0300, None), (0x0301, None), (0x01fb, Some(0x7f))],
+        // PLA
+        &[
+            (0x0301, None),
+            (0x0302, None),
+            (0x01fa, None),
+            (0x01fb, None),
+        ],
+        // RTS
+        &[
+            (0x0302, None),
+            (0x0303, None),
+            (0x01fb, None),
+            (0x01fc, None),
+            (0x01fd, None),
+            (0x020a, None),
+        ],
+    ];
+    for accesses in expected {
+        mos6502.memory_mut().clear();
+        mos6502.run().unwrap();
+        assert!(mos6502.memory().accesses() == accesses);
+        assert!(mos6502.last_run_cycles() == accesses.len() as u64);
+    }
+    assert!(mos6502.registers().pc() == TEST_START + program.len() as u16);
+}
+
+#[test]
+fn test_bus_access_kinds() {
+    use AccessKind::*;
+
+    let mut memory = BusLogMemory::default();
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x01,
+        encode_insn(Insn::INC(AddressMode::Absolute)),
+        0x00,
+        0x13,
+        encode_insn(Insn::JSR(AddressMode::Absolute)),
+        0x00,
+        0x03,
+        encode_insn(Insn::BRK),
+    ];
+    memory.memory.write(TEST_START, &program);
+    memory.memory.write_u8(0x0300, encode_insn(Insn::RTS));
+    memory.memory.write_u16(IRQ_BRK_VECTOR, 0x0400);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+
+    let expected: [&[AccessKind]; 6] = [
+        // LDA #$01
+        &[OpcodeFetch, OperandFetch],
+        // INC $1300: the old value written back is a dummy access
+        &[OpcodeFetch, OperandFetch, OperandFetch, Data, Dummy, Data],
+        // JSR $0300
+        &[OpcodeFetch, OperandFetch, Dummy, Stack, Stack, OperandFetch],
+        // RTS
+        &[OpcodeFetch, Dummy, Dummy, Stack, Stack, Dummy],
+        // BRK
+        &[OpcodeFetch, Dummy, Stack, Stack, Stack, Vector, Vector],
+        // IRQ
+        &[Dummy, Dummy, Stack, Stack, Stack, Vector, Vector],
+    ];
+    for (i, kinds) in expected.into_iter().enumerate() {
+        if i == 5 {
+            mos6502.registers_mut().clear_flag(Status::InterruptDisable);
+            mos6502.assert_irq(0).unwrap();
+        }
+        mos6502.memory_mut().clear();
+        mos6502.run().unwrap();
+        assert!(mos6502
+            .memory()
+            .kinds()
+            .iter()
+            .copied()
+            .eq(kinds.iter().copied().map(Some)));
+    }
+    assert!(mos6502.registers().pc() == 0x0400);
+}
+
+#[test]
+fn test_bus_accesses_match_cycles() {
+    for (variant, undocumented_opcodes, index) in [
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Disallow, 0x00),
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Disallow, 0x90),
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Allow, 0x00),
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Allow, 0x90),
+        (CpuVariant::Wdc65C02, UndocumentedOpcodes::Disallow, 0x00),
+        (CpuVariant::Wdc65C02, UndocumentedOpcodes::Disallow, 0x90),
+    ] {
+        for opcode in 0..=u8::MAX {
+            if variant == CpuVariant::Nmos6502 {
+                if !decode_undocumented_insn(opcode).is_valid() {
+                    continue;
+                }
+                if undocumented_opcodes == UndocumentedOpcodes::Disallow
+                    && !decode_insn(opcode).is_valid()
+                {
+                    continue;
+                }
+            }
+            // The bus activity of the eight-cycle NOP of the 65C02 isn't modeled
+            if variant == CpuVariant::Wdc65C02 && opcode == 0x5c {
+                continue;
+            }
+
+            let mut memory = BusLogMemory::default();
+            memory.memory.write(TEST_START, &[opcode, 0x80, 0x12]);
+
+            let mut regf = RegisterFile::default();
+            regf.set_pc(TEST_START);
+            *regf.x_mut() = index;
+            *regf.y_mut() = index;
+            *regf.sp_mut() = 0x80;
+
+            let mut mos6502 = Mos6502::with_variant(memory, variant, StackWraparound::Disallow);
+            *mos6502.registers_mut() = regf;
+            mos6502.set_bus_mode(BusMode::CycleStepped);
+            mos6502.set_undocumented_opcodes(undocumented_opcodes);
+            mos6502.run().unwrap();
+
+            assert!(mos6502.memory().accesses().len() as u64 == mos6502.last_run_cycles());
+        }
+    }
+}
+
 #[test]
 fn test_insn() {
     for g in 0..3_u8 {
@@ -409,11 +752,1598 @@ fn test_insn() {
     }
 }
 
+#[test]
+fn test_undocumented_insn() {
+    for opcode in 0..=u8::MAX {
+        let insn = decode_undocumented_insn(opcode);
+        if insn.is_valid() {
+            assert!(decode_undocumented_insn(encode_insn(insn)) == insn);
+        }
+    }
+
+    // Named as decoded
+    for (opcode, allowed) in [(0xa7, "LAX zpg"), (0x07, "SLO zpg"), (0x04, "NOP zpg")] {
+        let name = |undocumented| get_opcode_string_for(CpuVariant::Nmos6502, undocumented, opcode);
+        assert!(name(UndocumentedOpcodes::Allow) == allowed);
+        assert!(name(UndocumentedOpcodes::Disallow) == "JAM");
+    }
+    let name =
+        |opcode| get_opcode_string_for(CpuVariant::Nmos6502, UndocumentedOpcodes::Disallow, opcode);
+    assert!(name(0xa5) == "LDA zpg");
+    for opcode in 0..=u8::MAX {
+        assert!((name(opcode) != "JAM") == decode_insn(opcode).is_valid());
+    }
+}
+
+#[test]
+fn test_undocumented() {
+    let mut memory = TestMemory::default();
+
+    memory.write(0x10, &[0x81, 0x00, 0x82, 0x00, 0x40]);
+
+    let program = [
+        encode_insn(Insn::LAX(AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::LDX(AddressMode::Immediate)),
+        0x0f,
+        encode_insn(Insn::SAX(AddressMode::Zeropage)),
+        0x11,
+        encode_insn(Insn::DCP(AddressMode::Zeropage)),
+        0x12,
+        encode_insn(Insn::ISC(AddressMode::Zeropage)),
+        0x13,
+        encode_insn(Insn::SLO(AddressMode::Zeropage)),
+        0x14,
+        encode_insn(Insn::ANC(AddressMode::Immediate)),
+        0xff,
+        encode_insn(Insn::ALR(AddressMode::Immediate)),
+        0x81,
+        encode_insn(Insn::ARR(AddressMode::Immediate)),
+        0xff,
+        encode_insn(Insn::SBX(AddressMode::Immediate)),
+        0x01,
+        encode_insn(Insn::NOPM(AddressMode::AbsoluteX)),
+        0xf0,
+        0x12,
+    ];
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+
+    // Not allowed by default
+    let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+    assert!(mos6502.run() == Err(RunError::InvalidInstruction(program[0])));
+
+    let mut mos6502 =
+        Mos6502::with_registers(TestMemory::default(), regf, StackWraparound::Disallow);
+    mos6502
+        .memory_mut()
+        .write(0x10, &[0x81, 0x00, 0x82, 0x00, 0x40]);
+    mos6502.memory_mut().write(TEST_START, &program);
+    mos6502.set_undocumented_opcodes(UndocumentedOpcodes::Allow);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::LAX(AddressMode::Zeropage)));
+    assert!(mos6502.registers().a() == 0x81 && mos6502.registers().x() == 0x81);
+    assert!(mos6502.registers().flag_set(Status::Negative));
+
+    mos6502.run().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::SAX(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x11).unwrap() == 0x01);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::DCP(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x12).unwrap() == 0x81);
+    assert!(mos6502.registers().flag_set(Status::Zero));
+    assert!(mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::ISC(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x13).unwrap() == 0x01);
+    assert!(mos6502.registers().a() == 0x80);
+    assert!(mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::SLO(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x14).unwrap() == 0x80);
+    assert!(mos6502.registers().a() == 0x80);
+    assert!(!mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::ANC(AddressMode::Immediate)));
+    assert!(mos6502.registers().a() == 0x80);
+    assert!(mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::ALR(AddressMode::Immediate)));
+    assert!(mos6502.registers().a() == 0x40);
+    assert!(!mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::ARR(AddressMode::Immediate)));
+    assert!(mos6502.registers().a() == 0x20);
+    assert!(!mos6502.registers().flag_set(Status::Carry));
+    assert!(mos6502.registers().flag_set(Status::Overflow));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::SBX(AddressMode::Immediate)));
+    assert!(mos6502.registers().x() == 0xff);
+    assert!(mos6502.registers().flag_set(Status::Negative));
+    assert!(!mos6502.registers().flag_set(Status::Carry));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOPM(AddressMode::AbsoluteX)));
+    assert!(mos6502.last_run_cycles() == 5);
+    assert!(mos6502.registers().pc() == TEST_START + program.len() as u16);
+}
+
+#[test]
+fn test_65c02_insn() {
+    for opcode in 0..=u8::MAX {
+        let insn = decode_65c02_insn(opcode);
+        // The NOPs are encoded as the NMOS ones
+        if !matches!(insn, Insn::NOP | Insn::NOPM(_)) {
+            assert!(encode_insn(insn) == opcode);
+        }
+    }
+
+    let name =
+        |opcode| get_opcode_string_for(CpuVariant::Wdc65C02, UndocumentedOpcodes::Disallow, opcode);
+    assert!(name(0x80) == "BRA rel");
+    assert!(name(0x04) == "TSB zpg");
+    assert!(name(0x12) == "ORA (zpg)");
+    assert!(name(0x5a) == "PHY");
+    assert!(name(0x7c) == "JMP (abs,X)");
+    assert!(name(0x44) == "NOP zpg");
+    assert!(name(0xa9) == "LDA imm");
+    assert!(name(0x07) == "RMB0 zpg");
+    assert!(name(0xf7) == "SMB7 zpg");
+    assert!(name(0x0f) == "BBR0 zpg,rel");
+    assert!(name(0xff) == "BBS7 zpg,rel");
+    assert!(name(0xcb) == "WAI");
+    assert!(name(0xdb) == "STP");
+    for opcode in 0..=u8::MAX {
+        assert!(name(opcode) != "JAM");
+    }
+}
+
+#[test]
+fn test_65c02() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x0f,
+        encode_insn(Insn::STA(AddressMode::ZeropageIndirect)),
+        0x10,
+        encode_insn(Insn::STZ(AddressMode::Zeropage)),
+        0x20,
+        encode_insn(Insn::TSB(AddressMode::Zeropage)),
+        0x21,
+        encode_insn(Insn::TRB(AddressMode::Zeropage)),
+        0x22,
+        encode_insn(Insn::INCA),
+        encode_insn(Insn::BIT(AddressMode::Immediate)),
+        0xc0,
+        encode_insn(Insn::PHA),
+        encode_insn(Insn::PLX),
+        encode_insn(Insn::PHX),
+        encode_insn(Insn::PLY),
+        encode_insn(Insn::LDX(AddressMode::Immediate)),
+        0x02,
+        encode_insn(Insn::BRA(AddressMode::Relative)),
+        0x02,
+        encode_insn(Insn::NOP),
+        encode_insn(Insn::NOP),
+        encode_insn(Insn::JMP(AddressMode::AbsoluteXindirect)),
+        0x00,
+        0x14,
+    ];
+    let handler = [encode_insn(Insn::SED), encode_insn(Insn::BRK), 0x00];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write(0x0300, &handler);
+    memory.write_u16(0x10, 0x1300);
+    memory.write_u8(0x22, 0xf0);
+    memory.write_u16(0x1402, 0x0300);
+    memory.write_u16(IRQ_BRK_VECTOR, 0x0400);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    // The NMOS 6502 jams on the `(zp)` addressing mode
+    let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+    mos6502.run().unwrap();
+    assert!(mos6502.run() == Err(RunError::InvalidInstruction(program[2])));
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write(0x0300, &handler);
+    memory.write_u16(0x10, 0x1300);
+    memory.write_u8(0x22, 0xf0);
+    memory.write_u16(0x1402, 0x0300);
+    memory.write_u16(IRQ_BRK_VECTOR, 0x0400);
+
+    let mut mos6502 =
+        Mos6502::with_variant(memory, CpuVariant::Wdc65C02, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    mos6502.run().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::STA(AddressMode::ZeropageIndirect)));
+    assert!(mos6502.read_u8(0x1300).unwrap() == 0x0f);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::STZ(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x20).unwrap() == 0x00);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::TSB(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x21).unwrap() == 0x5f);
+    assert!(!mos6502.registers().flag_set(Status::Zero));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::TRB(AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x22).unwrap() == 0xf0);
+    assert!(mos6502.registers().flag_set(Status::Zero));
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::INCA));
+    assert!(mos6502.registers().a() == 0x10);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BIT(AddressMode::Immediate)));
+    assert!(mos6502.registers().flag_set(Status::Zero));
+    assert!(!mos6502.registers().flag_set(Status::Negative));
+    assert!(!mos6502.registers().flag_set(Status::Overflow));
+
+    for _ in 0..4 {
+        mos6502.run().unwrap();
+    }
+    assert!(mos6502.registers().x() == 0x10 && mos6502.registers().y() == 0x10);
+    assert!(mos6502.registers().sp() == 0xfd);
+
+    mos6502.run().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BRA(AddressMode::Relative)));
+    assert!(mos6502.last_run_cycles() == 3);
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JMP(AddressMode::AbsoluteXindirect)));
+    assert!(mos6502.last_run_cycles() == 6);
+    assert!(mos6502.registers().pc() == 0x0300);
+
+    // BRK clears the decimal flag, and the pushed status has it set
+    mos6502.run().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BRK));
+    assert!(mos6502.registers().pc() == 0x0400);
+    assert!(!mos6502.registers().flag_set(Status::Decimal));
+    assert!(mos6502.read_u8(STACK_BOTTOM + 0xfb).unwrap() & Status::Decimal.mask() != 0);
+}
+
+#[test]
+fn test_65c02_bits_wait_stop() {
+    let program = [
+        encode_insn(Insn::SMB(3, AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::RMB(0, AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::BBS(3, AddressMode::ZeropageRelative)),
+        0x10,
+        0x01,
+        encode_insn(Insn::NOP),
+        encode_insn(Insn::BBR(3, AddressMode::ZeropageRelative)),
+        0x10,
+        0x7f,
+        encode_insn(Insn::SEI),
+        encode_insn(Insn::WAI),
+        encode_insn(Insn::STP),
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u8(0x10, 0x01);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    let mut mos6502 =
+        Mos6502::with_variant(memory, CpuVariant::Wdc65C02, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::SMB(3, AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x09);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::RMB(0, AddressMode::Zeropage)));
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x08);
+    assert!(mos6502.last_run_cycles() == 5);
+
+    // Taken, skips the NOP
+    mos6502.run().unwrap();
+    assert!(mos6502.last_run_cycles() == 6);
+    assert!(mos6502.registers().pc() == TEST_START + 8);
+
+    // Not taken
+    mos6502.run().unwrap();
+    assert!(mos6502.last_run_cycles() == 5);
+    assert!(mos6502.registers().pc() == TEST_START + 11);
+
+    // WAI resumes on IRQ even with the interrupts disabled
+    mos6502.run().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::WAI));
+    assert!(mos6502.run().unwrap() == RunExit::Waiting);
+    assert!(mos6502.run().unwrap() == RunExit::Waiting);
+    assert!(mos6502.last_run_cycles() == 1);
+    mos6502.assert_irq(0).unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::STP));
+
+    // STP holds until reset
+    mos6502.assert_nmi().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Stopped);
+    assert!(mos6502.run().unwrap() == RunExit::Stopped);
+    mos6502.memory_mut().write_u16(RESET_VECTOR, TEST_START);
+    mos6502.set_reset_pending();
+    assert!(mos6502.run().unwrap() == RunExit::NonMaskableInterrupt);
+}
+
+#[test]
+fn test_ricoh_2a03() {
+    let program = [
+        encode_insn(Insn::SED),
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x09,
+        encode_insn(Insn::CLC),
+        encode_insn(Insn::ADC(AddressMode::Immediate)),
+        0x01,
+        encode_insn(Insn::SEC),
+        encode_insn(Insn::SBC(AddressMode::Immediate)),
+        0x11,
+    ];
+
+    for (variant, sum, difference) in [
+        (CpuVariant::Nmos6502, 0x10, 0x99),
+        (CpuVariant::Ricoh2A03, 0x0a, 0xf9),
+    ] {
+        let mut memory = TestMemory::default();
+        memory.write(TEST_START, &program);
+
+        let mut mos6502 = Mos6502::with_variant(memory, variant, StackWraparound::Disallow);
+        mos6502.registers_mut().set_pc(TEST_START);
+
+        for _ in 0..4 {
+            mos6502.run().unwrap();
+        }
+        assert!(mos6502.registers().a() == sum);
+        assert!(mos6502.registers().flag_set(Status::Decimal));
+
+        for _ in 0..2 {
+            mos6502.run().unwrap();
+        }
+        assert!(mos6502.registers().a() == difference);
+        assert!(!mos6502.registers().flag_set(Status::Carry));
+    }
+}
+
+#[test]
+fn test_mos6510_io_port() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0xef,
+        encode_insn(Insn::STA(AddressMode::Zeropage)),
+        0x00,
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0xc7,
+        encode_insn(Insn::STA(AddressMode::Zeropage)),
+        0x01,
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x2f,
+        encode_insn(Insn::STA(AddressMode::Zeropage)),
+        0x00,
+        encode_insn(Insn::LDA(AddressMode::Zeropage)),
+        0x01,
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut mos6502 = Mos6502::with_variant(memory, CpuVariant::Mos6510, StackWraparound::Disallow);
+    mos6502.registers_mut().set_pc(TEST_START);
+    mos6502.io_port_mut().unwrap().set_input_pins(0x00);
+
+    for _ in 0..7 {
+        mos6502.run().unwrap();
+    }
+
+    // The port is never seen by the memory
+    assert!(mos6502.memory().bytes[0] == 0x55 && mos6502.memory().bytes[1] == 0x55);
+
+    // The input bit 4 reads the pin, and the floating bits 6 and 7
+    // keep the value driven on them
+    let io_port = mos6502.io_port().unwrap();
+    assert!(io_port.ddr() == 0x2f && io_port.latch() == 0xc7);
+    assert!(io_port.pins() == 0x07);
+    assert!(mos6502.registers().a() == 0xc7);
+
+    // Pulled up
+    mos6502.io_port_mut().unwrap().set_input_pins(0xff);
+    assert!(mos6502.read_u8(IO_PORT_DATA).unwrap() == 0xd7);
+
+    // Not there for the other variants
+    let mut mos6502 = Mos6502::with_registers(
+        TestMemory::default(),
+        RegisterFile::new(),
+        StackWraparound::Disallow,
+    );
+    assert!(mos6502.io_port().is_none());
+    mos6502.write_u8(IO_PORT_DDR, 0x2f).unwrap();
+    assert!(mos6502.memory().bytes[0] == 0x2f);
+}
+
+#[test]
+fn test_mos6507() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Absolute)),
+        0x80,
+        0xf2,
+        encode_insn(Insn::STA(AddressMode::Absolute)),
+        0x81,
+        0x32,
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u16(RESET_VECTOR & 0x1fff, TEST_START | 0xe000);
+    memory.write_u8(0x1280, 0x42);
+
+    let mut mos6502 = Mos6502::with_variant(memory, CpuVariant::Mos6507, StackWraparound::Disallow);
+    assert!(mos6502.assert_irq(0) == Err(PinError::NotBonded));
+    assert!(mos6502.assert_nmi() == Err(PinError::NotBonded));
+
+    // The reset vector and the program are mirrored
+    mos6502.set_reset_pending();
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().pc() == TEST_START | 0xe003);
+    assert!(mos6502.registers().a() == 0x42);
+
+    mos6502.run().unwrap();
+    assert!(mos6502.memory().bytes[0x1281] == 0x42);
+}
+
+#[test]
+fn test_pointer_wrap() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::IndirectY)),
+        0xff,
+        encode_insn(Insn::JMP(AddressMode::Indirect)),
+        0xff,
+        0x12,
+    ];
+
+    for (variant, pointer_wrap, a, pc) in [
+        (CpuVariant::Nmos6502, PointerWrap::CrossPage, 0x14, 0x0400),
+        (CpuVariant::Nmos6502, PointerWrap::Hardware, 0x13, 0x0300),
+        (CpuVariant::Wdc65C02, PointerWrap::Hardware, 0x13, 0x0400),
+    ] {
+        let mut memory = TestMemory::default();
+        memory.write(TEST_START, &program);
+        memory.write(0x00ff, &[0x80, 0x14]);
+        memory.write_u8(0x0000, 0x13);
+        memory.write_u8(0x1380, 0x13);
+        memory.write_u8(0x1480, 0x14);
+        memory.write(0x12ff, &[0x00, 0x04]);
+        memory.write_u8(0x1200, 0x03);
+
+        let mut mos6502 = Mos6502::with_variant(memory, variant, StackWraparound::Disallow);
+        mos6502.registers_mut().set_pc(TEST_START);
+        *mos6502.registers_mut().y_mut() = 0;
+        mos6502.set_pointer_wrap(pointer_wrap);
+
+        mos6502.run().unwrap();
+        assert!(mos6502.registers().a() == a);
+        mos6502.run().unwrap();
+        assert!(mos6502.registers().pc() == pc);
+    }
+}
+
+#[test]
+fn test_reset_mode() {
+    let mut memory = BusLogMemory::default();
+    memory.memory.write_u16(RESET_VECTOR, TEST_START);
+    memory.memory.write_u8(TEST_START, encode_insn(Insn::NOP));
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.set_reset_mode(ResetMode::Hardware);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+
+    // At power-on, the stack pointer ends up at $FD
+    mos6502.set_reset_pending();
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().sp() == 0xfd);
+    assert!(mos6502.registers().pc() == TEST_START + 1);
+    assert!(mos6502.last_run_cycles() == RESET_CYCLES as u64 + 2);
+    assert!(
+        mos6502.memory().accesses()[2..7]
+            == [
+                (0x0100, None),
+                (0x01ff, None),
+                (0x01fe, None),
+                (RESET_VECTOR, None),
+                (RESET_VECTOR + 1, None),
+            ]
+    );
+
+    // Then it is decremented by 3 on every reset
+    mos6502.memory_mut().clear();
+    mos6502.set_reset_pending();
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().sp() == 0xfa);
+
+    mos6502.memory_mut().clear();
+    mos6502.set_reset_mode(ResetMode::Simplified);
+    mos6502.set_reset_pending();
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().sp() == 0xfa);
+
+    /// Fails to read the reset vector the first times
+    struct FailingVectorMemory {
+        memory: TestMemory,
+        failures: u32,
+    }
+
+    impl Memory for FailingVectorMemory {
+        fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+            Memory::write(&mut self.memory, addr, value)
+        }
+
+        fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
+            if addr == RESET_VECTOR && self.failures != 0 {
+                self.failures -= 1;
+                return Err(crate::MemoryError::BadAddress(addr));
+            }
+            Memory::read(&mut self.memory, addr)
+        }
+    }
+
+    // A failed reset leaves the processor as it was, the retry
+    // decrements the stack pointer only once
+    let mut memory = FailingVectorMemory {
+        memory: TestMemory::default(),
+        failures: 3,
+    };
+    memory.memory.write_u16(RESET_VECTOR, TEST_START);
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.set_reset_mode(ResetMode::Hardware);
+    mos6502.set_reset_pending();
+    let snapshot = mos6502.snapshot();
+    for _ in 0..3 {
+        assert!(
+            mos6502.run()
+                == Err(RunError::MemoryAccess(crate::MemoryError::BadAddress(
+                    RESET_VECTOR
+                )))
+        );
+        assert!(mos6502.snapshot() == snapshot);
+    }
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().sp() == 0xfd);
+}
+
+#[test]
+fn test_interrupt_lines() {
+    let program = [encode_insn(Insn::CLI), encode_insn(Insn::NOP)];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u16(IRQ_BRK_VECTOR, TEST_START + 1);
+    memory.write_u16(NMI_VECTOR, TEST_START + 1);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+    regf.set_flag(Status::InterruptDisable);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    // The handler is entered for as long as any source holds the line
+    mos6502.assert_irq(3).unwrap();
+    mos6502.assert_irq(17).unwrap();
+    assert!(mos6502.irq_sources() == 1 << 3 | 1 << 17);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::CLI));
+    assert!(mos6502.run().unwrap() == RunExit::Interrupt);
+    mos6502.registers_mut().clear_flag(Status::InterruptDisable);
+    mos6502.release_irq(3).unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Interrupt);
+    mos6502.registers_mut().clear_flag(Status::InterruptDisable);
+    mos6502.release_irq(17).unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+    assert!(mos6502.assert_irq(IRQ_SOURCES) == Err(PinError::NoSuchSource));
+
+    // NMI is taken once on the falling edge
+    mos6502.assert_nmi().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::NonMaskableInterrupt);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+    mos6502.assert_nmi().unwrap();
+    mos6502.release_nmi().unwrap();
+    mos6502.assert_nmi().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::NonMaskableInterrupt);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+}
+
+#[test]
+fn test_interrupt_timing() {
+    let program = [
+        encode_insn(Insn::CLI),
+        encode_insn(Insn::NOP),
+        encode_insn(Insn::SEI),
+        encode_insn(Insn::BRK),
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u16(IRQ_BRK_VECTOR, TEST_START + 2);
+    memory.write_u16(NMI_VECTOR, TEST_START + 3);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+    regf.set_flag(Status::InterruptDisable);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    // The IRQ is taken right after CLI
+    mos6502.assert_irq(0).unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::CLI));
+    assert!(mos6502.run().unwrap() == RunExit::Interrupt);
+    assert!(mos6502.read_u16(0x01fc).unwrap() == TEST_START + 1);
+
+    // The IRQ is delayed by one instruction after CLI
+    *mos6502.registers_mut() = regf;
+    mos6502.set_interrupt_timing(InterruptTiming::Hardware);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::CLI));
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+    assert!(mos6502.run().unwrap() == RunExit::Interrupt);
+    assert!(mos6502.read_u16(0x01fc).unwrap() == TEST_START + 2);
+
+    // SEI lets the IRQ in, the I flag is pushed set
+    let mut regs = regf;
+    regs.set_pc(TEST_START + 2);
+    regs.clear_flag(Status::InterruptDisable);
+    *mos6502.registers_mut() = regs;
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::SEI));
+    assert!(mos6502.run().unwrap() == RunExit::Interrupt);
+    assert!(mos6502.read_u8(0x01fb).unwrap() & Status::InterruptDisable.mask() != 0);
+
+    mos6502.release_irq(0).unwrap();
+
+    // An NMI arriving during BRK takes over the vector, B stays set
+    regs.set_pc(TEST_START + 3);
+    *mos6502.registers_mut() = regs;
+    mos6502.assert_nmi().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BRK));
+    assert!(mos6502.registers().pc() == TEST_START + 3);
+    assert!(mos6502.read_u8(0x01fb).unwrap() & Status::Break.mask() != 0);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BRK));
+    assert!(mos6502.registers().pc() == TEST_START + 2);
+}
+
+#[test]
+fn test_so_pin() {
+    // BVC *, polling for the byte ready
+    let program = [encode_insn(Insn::BVC(AddressMode::Relative)), 0xfe];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    for _ in 0..4 {
+        mos6502.run().unwrap();
+        assert!(mos6502.registers().pc() == TEST_START);
+    }
+    mos6502.pulse_so().unwrap();
+    mos6502.run().unwrap();
+    assert!(mos6502.registers().pc() == TEST_START + 2);
+    assert!(mos6502.registers().flag_set(Status::Overflow));
+
+    let mut mos6510 = Mos6502::with_variant(
+        TestMemory::default(),
+        CpuVariant::Mos6510,
+        StackWraparound::Disallow,
+    );
+    assert!(mos6510.pulse_so() == Err(PinError::NotBonded));
+}
+
+#[test]
+fn test_rdy() {
+    let program = [encode_insn(Insn::NOP), encode_insn(Insn::NOP)];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    // The sprite DMA
+    mos6502.steal_cycles(513);
+    assert!(mos6502.run().unwrap() == RunExit::Stalled(513));
+    assert!(mos6502.last_run_cycles() == 513);
+    assert!(mos6502.registers().pc() == TEST_START);
+
+    // Halted for as long as RDY is low
+    mos6502.set_rdy(false);
+    for _ in 0..3 {
+        assert!(mos6502.run().unwrap() == RunExit::Stalled(1));
+    }
+    assert!(mos6502.cycles() == 516);
+    mos6502.set_rdy(true);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+    assert!(mos6502.registers().pc() == TEST_START + 1);
+
+    /// Holds RDY low for the cycles after the reads
+    struct DmaMemory {
+        memory: TestMemory,
+        reads: u32,
+        halt_after: u32,
+        halt_cycles: u32,
+    }
+
+    impl Memory for DmaMemory {
+        fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+            Memory::write(&mut self.memory, addr, value)
+        }
+
+        fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
+            self.reads += 1;
+            Memory::read(&mut self.memory, addr)
+        }
+
+        fn stall_cycles(&mut self) -> u32 {
+            if self.reads == self.halt_after {
+                core::mem::take(&mut self.halt_cycles)
+            } else {
+                0
+            }
+        }
+    }
+
+    // Halted in the middle of LDA $1300, on fetching the high byte of the address
+    let mut memory = DmaMemory {
+        memory: TestMemory::default(),
+        reads: 0,
+        halt_after: 2,
+        halt_cycles: 3,
+    };
+    memory.memory.write(
+        TEST_START,
+        &[encode_insn(Insn::LDA(AddressMode::Absolute)), 0x00, 0x13],
+    );
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.registers_mut().set_pc(TEST_START);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::LDA(AddressMode::Absolute)));
+    assert!(mos6502.last_run_cycles() == 4 + 3);
+    assert!(mos6502.memory().halt_cycles == 0);
+
+    // The host reading between the instructions leaves the stall
+    // to the next instruction
+    let mut memory = DmaMemory {
+        memory: TestMemory::default(),
+        reads: 0,
+        halt_after: 1,
+        halt_cycles: 100,
+    };
+    memory.memory.write(TEST_START, &program);
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.registers_mut().set_pc(TEST_START);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+    mos6502.read_u8(0x1300).unwrap();
+    assert!(mos6502.cycles() == 0);
+    assert!(mos6502.run().unwrap() == RunExit::Stalled(100));
+}
+
+#[test]
+fn test_write_mode() {
+    let program = [encode_insn(Insn::JSR(AddressMode::Absolute)), 0x34, 0x12];
+
+    // The first byte of the return address is pushed at $01FF,
+    // the stack overflows with the second one
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xff;
+
+    let new_mos6502 = |write_mode| {
+        let mut memory = TestMemory::default();
+        memory.write(TEST_START, &program);
+
+        let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+        mos6502.set_write_mode(write_mode);
+        mos6502
+    };
+
+    let mut mos6502 = new_mos6502(WriteMode::Immediate);
+    assert!(mos6502.run() == Err(RunError::StackOverflow));
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.read_u8(0x01ff).unwrap() == 0x02);
+
+    // The faulted instruction leaves the memory as it was
+    let mut mos6502 = new_mos6502(WriteMode::Transactional);
+    assert!(mos6502.run() == Err(RunError::StackOverflow));
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.read_u8(0x01ff).unwrap() == 0x55);
+
+    // So does the interrupt entry
+    let mut mos6502 = new_mos6502(WriteMode::Transactional);
+    mos6502.assert_irq(0).unwrap();
+    assert!(mos6502.run() == Err(RunError::StackOverflow));
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.read_u8(0x01ff).unwrap() == 0x55);
+
+    // The writes are made once the instruction retires
+    let mut mos6502 = new_mos6502(WriteMode::Transactional);
+    *mos6502.registers_mut().sp_mut() = 0xfd;
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JSR(AddressMode::Absolute)));
+    assert!(mos6502.read_u16(0x01fc).unwrap() == TEST_START + 2);
+
+    /// Refuses the writes to the address
+    struct RomMemory {
+        memory: TestMemory,
+        rom: u16,
+    }
+
+    impl Memory for RomMemory {
+        fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+            self.check_write(addr)?;
+            Memory::write(&mut self.memory, addr, value)
+        }
+
+        fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
+            Memory::read(&mut self.memory, addr)
+        }
+
+        fn check_write(&self, addr: u16) -> Result<(), crate::MemoryError> {
+            if addr == self.rom {
+                return Err(crate::MemoryError::ReadOnlyAddress(addr));
+            }
+            Ok(())
+        }
+    }
+
+    // The second byte of the return address hits the read-only memory,
+    // and the first one is not written either
+    let mut memory = RomMemory {
+        memory: TestMemory::default(),
+        rom: 0x01fd,
+    };
+    memory.memory.write(TEST_START, &program);
+    let mut mos6502 = Mos6502::with_registers(memory, regf, StackWraparound::Disallow);
+    mos6502.set_write_mode(WriteMode::Transactional);
+    *mos6502.registers_mut().sp_mut() = 0xfe;
+    let regf = *mos6502.registers();
+    assert!(
+        mos6502.run()
+            == Err(RunError::MemoryAccess(crate::MemoryError::ReadOnlyAddress(
+                0x01fd
+            )))
+    );
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.read_u8(0x01fe).unwrap() == 0x55);
+
+    // The NMI entry the memory refuses leaves the NMI pending
+    mos6502.set_reset_pending();
+    mos6502.run().unwrap();
+    *mos6502.registers_mut() = regf;
+    mos6502.assert_nmi().unwrap();
+    assert!(
+        mos6502.run()
+            == Err(RunError::MemoryAccess(crate::MemoryError::ReadOnlyAddress(
+                0x01fd
+            )))
+    );
+    assert!(*mos6502.registers() == regf);
+    mos6502.memory_mut().rom = 0;
+    mos6502.set_reset_pending();
+    assert!(mos6502.run().unwrap() == RunExit::NonMaskableInterrupt);
+}
+
+#[test]
+fn test_jam_policy() {
+    // A halting opcode, and an unstable one with the absolute operand
+    let program = [0x02, 0x9c, 0x34, 0x12, encode_insn(Insn::NOP)];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+
+    // The host emulates the opcode as the load of the immediate operand
+    const HANDLER_CYCLES: u8 = 3;
+    struct LoadHost {
+        jams: usize,
+    }
+    impl Host<TestMemory> for LoadHost {
+        fn jam(&mut self, _opcode: u8, bus: &mut HostBus<'_, TestMemory>) -> Result<u8, RunError> {
+            self.jams += 1;
+            let pc = bus.registers().pc();
+            *bus.registers_mut().a_mut() = bus.read_u8(pc)?;
+            bus.registers_mut().adjust_pc_by(1);
+            Ok(HANDLER_CYCLES)
+        }
+    }
+
+    let mut mos6502 = Mos6502::with_host(
+        memory,
+        LoadHost { jams: 0 },
+        CpuVariant::Nmos6502,
+        StackWraparound::Disallow,
+    );
+    *mos6502.registers_mut() = regf;
+
+    mos6502.set_jam_policy(JamPolicy::Nop);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JAM));
+    assert!(mos6502.registers().pc() == TEST_START + 1);
+    assert!(mos6502.last_run_cycles() == 2);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JAM));
+    assert!(mos6502.registers().pc() == TEST_START + 4);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+
+    assert!(mos6502.host().jams == 0);
+
+    *mos6502.registers_mut() = regf;
+    mos6502.set_jam_policy(JamPolicy::Host);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JAM));
+    assert!(mos6502.registers().a() == 0x9c);
+    assert!(mos6502.registers().pc() == TEST_START + 2);
+    assert!(mos6502.last_run_cycles() == HANDLER_CYCLES as u64);
+    assert!(mos6502.host().jams == 1);
+
+    // Only reset helps
+    *mos6502.registers_mut() = regf;
+    mos6502.set_jam_policy(JamPolicy::Freeze);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::JAM));
+    assert!(mos6502.run().unwrap() == RunExit::Stopped);
+    assert!(mos6502.run().unwrap() == RunExit::Stopped);
+
+    assert!(get_operand_bytes(encode_insn(Insn::BRK)) == 1);
+    assert!(get_operand_bytes(encode_insn(Insn::JSR(AddressMode::Absolute))) == 2);
+    assert!(get_operand_bytes(encode_insn(Insn::RTS)) == 0);
+    assert!(get_operand_bytes(encode_insn(Insn::LDX(AddressMode::Immediate))) == 1);
+    assert!(get_operand_bytes(encode_insn(Insn::LDX(AddressMode::AbsoluteY))) == 2);
+}
+
+#[test]
+fn test_traps() {
+    let program = [
+        encode_insn(Insn::BRK),
+        0x42,
+        0x02,
+        0x07,
+        encode_insn(Insn::NOP),
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    // Stores the signature at $10 and returns it in A, $ff fails
+    // after storing it
+    struct Syscalls {
+        signatures: [u8; 2],
+        calls: usize,
+    }
+    impl Host<TestMemory> for Syscalls {
+        fn trap(
+            &mut self,
+            opcode: u8,
+            signature: u8,
+            bus: &mut HostBus<'_, TestMemory>,
+        ) -> Result<(), RunError> {
+            *bus.registers_mut().a_mut() = signature;
+            bus.write_u8(0x10, signature)?;
+            if signature == 0xff {
+                return Err(RunError::InvalidInstruction(opcode));
+            }
+            self.signatures[self.calls] = signature;
+            self.calls += 1;
+            Ok(())
+        }
+    }
+
+    let host = Syscalls {
+        signatures: [0; 2],
+        calls: 0,
+    };
+    let mut mos6502 = Mos6502::with_host(
+        memory,
+        host,
+        CpuVariant::Nmos6502,
+        StackWraparound::Disallow,
+    );
+    *mos6502.registers_mut() = regf;
+    mos6502.set_brk_trap(true);
+    mos6502.set_opcode_trap(Some(0x02));
+
+    assert!(mos6502.run().unwrap() == RunExit::Trapped(0x42));
+    assert!(mos6502.registers().a() == 0x42);
+    assert!(mos6502.registers().pc() == TEST_START + 2);
+    assert!(mos6502.registers().sp() == 0xfd);
+    assert!(mos6502.last_run_cycles() == 2);
+    assert!(mos6502.run().unwrap() == RunExit::Trapped(0x07));
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x07);
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::NOP));
+    assert!(mos6502.host().signatures == [0x42, 0x07]);
+
+    // The interrupt is polled after the trap as after an instruction
+    mos6502.set_interrupt_timing(InterruptTiming::Hardware);
+    mos6502.host_mut().calls = 0;
+    *mos6502.registers_mut() = regf;
+    mos6502.assert_nmi().unwrap();
+    assert!(mos6502.run().unwrap() == RunExit::Trapped(0x42));
+    assert!(mos6502.run().unwrap() == RunExit::NonMaskableInterrupt);
+    mos6502.release_nmi().unwrap();
+    mos6502.set_interrupt_timing(InterruptTiming::Immediate);
+
+    // Without the trap, BRK vectors through IRQ_BRK_VECTOR
+    mos6502
+        .memory_mut()
+        .write_u16(IRQ_BRK_VECTOR, TEST_START + 4);
+    mos6502.set_brk_trap(false);
+    *mos6502.registers_mut() = regf;
+    assert!(mos6502.run().unwrap() == RunExit::Executed(Insn::BRK));
+    assert!(mos6502.registers().pc() == TEST_START + 4);
+
+    // The failing host function is rolled back with its writes
+    mos6502.set_write_mode(WriteMode::Transactional);
+    mos6502.memory_mut().write_u8(TEST_START + 3, 0xff);
+    *mos6502.registers_mut() = regf;
+    mos6502.registers_mut().set_pc(TEST_START + 2);
+    let regf = *mos6502.registers();
+    assert!(mos6502.run() == Err(RunError::InvalidInstruction(0x02)));
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x42);
+}
+
+#[test]
+fn test_snapshot() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x42,
+        encode_insn(Insn::STA(AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::INX),
+        encode_insn(Insn::NOP),
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    regf.set_flag(Status::InterruptDisable);
+
+    // Warm up the machine
+    let mut mos6502 =
+        Mos6502::with_variant(memory, CpuVariant::Wdc65C02, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+    mos6502.set_interrupt_timing(InterruptTiming::Hardware);
+    mos6502.run().unwrap();
+    mos6502.run().unwrap();
+    mos6502.assert_irq(5).unwrap();
+
+    let mut bytes = [0; SNAPSHOT_SIZE + 4 + MAX_MEMORY_SIZE];
+    assert!(mos6502.save_machine(&mut bytes[..100]) == Err(SnapshotError::BufferTooSmall));
+    assert!(mos6502.save_machine(&mut bytes) == Ok(bytes.len()));
+
+    // Fork
+    let mut fork = Mos6502::new(TestMemory::default(), StackWraparound::Allow);
+    fork.restore_machine(&bytes).unwrap();
+    assert!(fork.snapshot() == mos6502.snapshot());
+    assert!(fork.variant() == CpuVariant::Wdc65C02);
+    assert!(fork.bus_mode() == BusMode::CycleStepped);
+    assert!(fork.irq_sources() == 1 << 5);
+    assert!(fork.read_u8(0x10).unwrap() == 0x42);
+    for _ in 0..2 {
+        assert!(fork.run() == mos6502.run());
+        assert!(*fork.registers() == *mos6502.registers());
+        assert!(fork.cycles() == mos6502.cycles());
+    }
+
+    // The header and the state are checked
+    let snapshot = mos6502.snapshot();
+    let mut corrupted = [0; SNAPSHOT_SIZE];
+    corrupted.copy_from_slice(snapshot.as_bytes());
+    assert!(Snapshot::from_bytes(&corrupted[..10]) == Err(SnapshotError::Truncated));
+    corrupted[4] = 0xff;
+    assert!(Snapshot::from_bytes(&corrupted) == Err(SnapshotError::UnsupportedVersion(0xff)));
+    corrupted[0] = 0;
+    assert!(Snapshot::from_bytes(&corrupted) == Err(SnapshotError::BadMagic));
+    corrupted.copy_from_slice(snapshot.as_bytes());
+    // The variant, found as the only byte telling apart the processors
+    // differing in it alone
+    let nmos = Mos6502::new(TestMemory::default(), StackWraparound::Allow).snapshot();
+    let cmos = Mos6502::with_variant(
+        TestMemory::default(),
+        CpuVariant::Wdc65C02,
+        StackWraparound::Allow,
+    )
+    .snapshot();
+    let mut differing = (0..SNAPSHOT_SIZE).filter(|&i| nmos.as_bytes()[i] != cmos.as_bytes()[i]);
+    let variant_offset = differing.next().unwrap();
+    assert!(differing.next().is_none());
+    corrupted[variant_offset] = 0xff;
+    let corrupted = Snapshot::from_bytes(&corrupted).unwrap();
+    assert!(fork.restore(&corrupted) == Err(SnapshotError::Invalid));
+    assert!(fork.snapshot() == mos6502.snapshot());
+
+    // The processor is left as it is if the memory refuses the contents
+    let mut reference = Mos6502::new(TestMemory::default(), StackWraparound::Allow);
+    let saved = reference.snapshot();
+    let contents_len = MAX_MEMORY_SIZE as u32 - 1;
+    bytes[SNAPSHOT_SIZE..SNAPSHOT_SIZE + 4].copy_from_slice(&contents_len.to_le_bytes());
+    assert!(reference.restore_machine(&bytes) == Err(SnapshotError::Invalid));
+    assert!(reference.snapshot() == saved);
+}
+
+#[test]
+fn test_undo_history() {
+    let program = [
+        encode_insn(Insn::LDA(AddressMode::Immediate)),
+        0x01,
+        encode_insn(Insn::STA(AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::INC(AddressMode::Zeropage)),
+        0x10,
+        encode_insn(Insn::JSR(AddressMode::Absolute)),
+        0x00,
+        0x03,
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u8(0x0300, encode_insn(Insn::NOP));
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.set_bus_mode(BusMode::CycleStepped);
+    *mos6502.registers_mut() = regf;
+
+    let mut history = UndoHistory::<8>::new();
+    for _ in 0..5 {
+        mos6502.run_recorded(&mut history).unwrap();
+    }
+    assert!(history.len() == 5);
+    assert!(mos6502.registers().pc() == 0x0301);
+    assert!(mos6502.read_u16(0x01fc).unwrap() == TEST_START + 8);
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x02);
+
+    // Back over the NOP and JSR
+    assert!(mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.registers().pc() == TEST_START + 6);
+    assert!(mos6502.registers().sp() == 0xfd);
+    assert!(mos6502.read_u16(0x01fc).unwrap() == 0x5555);
+
+    // Back to the start
+    assert!(mos6502
+        .run_back_until(&mut history, |mos6502| mos6502.registers().pc()
+            == TEST_START)
+        .unwrap());
+    assert!(*mos6502.registers() == regf);
+    assert!(mos6502.cycles() == 0);
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x55);
+    assert!(!mos6502.step_back(&mut history).unwrap());
+
+    // The oldest records are dropped
+    let mut history = UndoHistory::<2>::new();
+    for _ in 0..4 {
+        mos6502.run_recorded(&mut history).unwrap();
+    }
+    assert!(history.len() == 2);
+    assert!(!mos6502
+        .run_back_until(&mut history, |mos6502| mos6502.registers().pc()
+            == TEST_START)
+        .unwrap());
+    assert!(mos6502.registers().pc() == TEST_START + 4);
+    assert!(mos6502.read_u8(0x10).unwrap() == 0x01);
+
+    // The host fills the memory from the signature byte on, through
+    // the bus or directly
+    struct Fill;
+    impl Host<TestMemory> for Fill {
+        fn trap(
+            &mut self,
+            _opcode: u8,
+            signature: u8,
+            bus: &mut HostBus<'_, TestMemory>,
+        ) -> Result<(), RunError> {
+            for addr in signature as u16..0x20 {
+                if signature == 0x1f {
+                    bus.memory_mut().bytes[addr as usize] = 0;
+                } else {
+                    bus.write_u8(addr, 0)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &[encode_insn(Insn::BRK), 0x1e]);
+    memory.write(TEST_START + 2, &[encode_insn(Insn::BRK), 0x00]);
+    memory.write(TEST_START + 4, &[encode_insn(Insn::BRK), 0x1f]);
+    let mut mos6502 = Mos6502::with_host(
+        memory,
+        Fill,
+        CpuVariant::Nmos6502,
+        StackWraparound::Disallow,
+    );
+    mos6502.set_brk_trap(true);
+    *mos6502.registers_mut() = regf;
+
+    let mut history = UndoHistory::<4>::new();
+    mos6502.run_recorded(&mut history).unwrap();
+    assert!(history.can_step_back());
+    assert!(mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.read_u8(0x1e).unwrap() == 0x55);
+    assert!(*mos6502.registers() == regf);
+
+    // More writes than recorded
+    mos6502.registers_mut().set_pc(TEST_START + 2);
+    mos6502.run_recorded(&mut history).unwrap();
+    assert!(!history.can_step_back());
+    assert!(!mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.registers().pc() == TEST_START + 4);
+    assert!(history.len() == 1);
+
+    // Written directly
+    history.clear();
+    mos6502.memory_mut().bytes[0x1f] = 0x55;
+    mos6502.run_recorded(&mut history).unwrap();
+    assert!(mos6502.read_u8(0x1f).unwrap() == 0);
+    assert!(!mos6502.step_back(&mut history).unwrap());
+}
+
+#[test]
+fn test_peek_poke() {
+    let mut memory = BusLogMemory::default();
+    memory.memory.write(
+        TEST_START,
+        &[
+            encode_insn(Insn::LDA(AddressMode::Immediate)),
+            0x01,
+            encode_insn(Insn::STA(AddressMode::Absolute)),
+            0x00,
+            0x13,
+        ],
+    );
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.registers_mut().set_pc(TEST_START);
+    mos6502.run().unwrap();
+
+    // Recording the history doesn't read the old value from the bus,
+    // and stepping back doesn't write it there
+    let mut history = UndoHistory::<2>::new();
+    mos6502.memory_mut().clear();
+    mos6502.run_recorded(&mut history).unwrap();
+    assert!(
+        mos6502.memory().accesses()
+            == [
+                (0x0202, None),
+                (0x0203, None),
+                (0x0204, None),
+                (0x1300, Some(0x01))
+            ]
+    );
+    mos6502.memory_mut().clear();
+    assert!(mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.peek_u8(0x1300) == Some(0x55));
+    mos6502.poke_u8(0x1300, 0x42).unwrap();
+    assert!(mos6502.peek_u16(0x1300) == Some(0x5542));
+    mos6502.poke_u16(0x1300, 0x1234).unwrap();
+    assert!(mos6502.peek_u16(0x1300) == Some(0x1234));
+    assert!(mos6502.memory().accesses().is_empty());
+    // Without `Memory::peek`, the old value is read to be recorded
+    struct Unpeekable(TestMemory);
+    impl Memory for Unpeekable {
+        fn write(&mut self, addr: u16, value: u8) -> Result<(), crate::MemoryError> {
+            Memory::write(&mut self.0, addr, value)
+        }
+
+        fn read(&mut self, addr: u16) -> Result<u8, crate::MemoryError> {
+            Memory::read(&mut self.0, addr)
+        }
+    }
+    let mut memory = Unpeekable(TestMemory::default());
+    memory.0.write(
+        TEST_START,
+        &[encode_insn(Insn::STA(AddressMode::Zeropage)), 0x10],
+    );
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    mos6502.registers_mut().set_pc(TEST_START);
+    let mut history = UndoHistory::<2>::new();
+    *mos6502.registers_mut().a_mut() = 0x42;
+    mos6502.run_recorded(&mut history).unwrap();
+    assert!(mos6502.peek_u8(0x10).is_none());
+    assert!(mos6502.memory().0.bytes[0x10] == 0x42);
+    assert!(history.can_step_back());
+    assert!(mos6502.step_back(&mut history).unwrap());
+    assert!(mos6502.memory().0.bytes[0x10] == 0x55);
+    assert!(mos6502.registers().pc() == TEST_START);
+}
+
+#[test]
+fn test_run_batch() {
+    let program = [
+        encode_insn(Insn::INX),
+        encode_insn(Insn::INY),
+        encode_insn(Insn::JMP(AddressMode::Absolute)),
+        0x00,
+        0x02,
+    ];
+
+    let mut memory = TestMemory::default();
+    memory.write(TEST_START, &program);
+    memory.write_u16(IRQ_BRK_VECTOR, TEST_START);
+
+    let mut regf = RegisterFile::default();
+    regf.set_pc(TEST_START);
+    *regf.sp_mut() = 0xfd;
+    *regf.x_mut() = 0;
+    regf.clear_flag(Status::InterruptDisable);
+
+    let mut mos6502 = Mos6502::new(memory, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+
+    // INX, INY and JMP take 7 cycles
+    let summary = mos6502.run_batch(Budget::Instructions(10), &[]);
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    assert!(summary.instructions == 10);
+    assert!(summary.cycles == 3 * 7 + 2);
+    assert!(mos6502.registers().x() == 4);
+
+    let summary = mos6502.run_batch(Budget::Cycles(14), &[]);
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    assert!(summary.instructions == 6);
+    assert!(summary.cycles == 14);
+
+    let summary = mos6502.run_batch(Budget::Cycles(1000), &[TEST_START + 2]);
+    assert!(summary.exit == BatchExit::StopPc(TEST_START + 2));
+    assert!(summary.instructions == 1);
+
+    mos6502.assert_irq(0).unwrap();
+    let summary = mos6502.run_batch(Budget::Cycles(1000), &[]);
+    assert!(summary.exit == BatchExit::Event(RunExit::Interrupt));
+    assert!(summary.instructions == 0);
+    assert!(summary.cycles == INTERRUPT_CYCLES as u64);
+
+    // The stolen cycles are charged against the budget
+    mos6502.steal_cycles(5);
+    let summary = mos6502.run_batch(Budget::Instructions(3), &[]);
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    assert!(summary.instructions == 3);
+    assert!(summary.cycles == 5 + 7);
+
+    mos6502.memory_mut().write_u8(TEST_START + 1, 0x02);
+    let summary = mos6502.run_batch(Budget::Cycles(1000), &[]);
+    assert!(summary.exit == BatchExit::Fault(RunError::InvalidInstruction(0x02)));
+    assert!(summary.instructions == 1);
+
+    // The IRQ masked at first is taken once CLI clears the flag, and
+    // the handler waits for the next one
+    let mut memory = TestMemory::default();
+    memory.write(
+        TEST_START,
+        &[
+            encode_insn(Insn::NOP),
+            encode_insn(Insn::CLI),
+            encode_insn(Insn::NOP),
+        ],
+    );
+    memory.write(0x0300, &[encode_insn(Insn::WAI), encode_insn(Insn::NOP)]);
+    memory.write_u16(IRQ_BRK_VECTOR, 0x0300);
+
+    let mut mos6502 =
+        Mos6502::with_variant(memory, CpuVariant::Wdc65C02, StackWraparound::Disallow);
+    *mos6502.registers_mut() = regf;
+    mos6502.registers_mut().set_flag(Status::InterruptDisable);
+    mos6502.assert_irq(0).unwrap();
+
+    let summary = mos6502.run_batch(Budget::Instructions(10), &[]);
+    assert!(summary.exit == BatchExit::Event(RunExit::Interrupt));
+    assert!(summary.instructions == 2);
+    mos6502.release_irq(0).unwrap();
+
+    // Nothing wakes the processor up during the batch
+    let summary = mos6502.run_batch(Budget::Cycles(100), &[]);
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    assert!(summary.instructions == 1);
+    assert!(summary.cycles == 100);
+    let summary = mos6502.run_batch(Budget::Instructions(5), &[]);
+    assert!(summary.exit == BatchExit::Event(RunExit::Waiting));
+    assert!(summary.cycles == 1);
+
+    // Nor lets it go on with RDY low
+    mos6502.assert_irq(0).unwrap();
+    mos6502.set_rdy(false);
+    let summary = mos6502.run_batch(Budget::Cycles(50), &[]);
+    assert!(summary.exit == BatchExit::BudgetExhausted);
+    assert!(summary.cycles == 50);
+    let summary = mos6502.run_batch(Budget::Instructions(5), &[]);
+    assert!(summary.exit == BatchExit::Event(RunExit::Stalled(1)));
+    mos6502.set_rdy(true);
+    let summary = mos6502.run_batch(Budget::Instructions(1), &[]);
+    assert!(summary.instructions == 1);
+    assert!(mos6502.registers().pc() == 0x0302);
+}
+
+#[test]
+fn test_opcode_handlers() {
+    let instruction_sets = [
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Disallow),
+        (CpuVariant::Nmos6502, UndocumentedOpcodes::Allow),
+        (CpuVariant::Wdc65C02, UndocumentedOpcodes::Disallow),
+    ];
+
+    // Every handler in the tables executes the instruction its opcode decodes to
+    for (variant, undocumented_opcodes) in instruction_sets {
+        for opcode in 0..=0xff {
+            let (insn, cycles) = match (variant, undocumented_opcodes) {
+                (CpuVariant::Wdc65C02, _) => {
+                    (decode_65c02_insn(opcode), get_65c02_opcode_cycles(opcode))
+                }
+                (_, UndocumentedOpcodes::Allow) => {
+                    (decode_undocumented_insn(opcode), get_opcode_cycles(opcode))
+                }
+                (_, UndocumentedOpcodes::Disallow) => {
+                    (decode_insn(opcode), get_opcode_cycles(opcode))
+                }
+            };
+
+            let mut memory = TestMemory::default();
+            memory.write_u8(TEST_START, opcode);
+
+            let mut mos6502 = Mos6502::with_variant(memory, variant, StackWraparound::Allow);
+            mos6502.registers_mut().set_pc(TEST_START);
+            mos6502.set_undocumented_opcodes(undocumented_opcodes);
+            mos6502.set_jam_policy(JamPolicy::Nop);
+
+            assert!(mos6502.run().unwrap() == RunExit::Executed(insn));
+            assert!(mos6502.last_run_cycles() >= cycles as u64);
+        }
+    }
+}
+
 #[test]
 fn test_bcd() {
-    let x = bcd_to_u8(0x89);
-    assert!(x == 89);
+    fn to_bcd(u: u16) -> u8 {
+        (((u / 10) << 4) | (u % 10)) as u8
+    }
+
+    // The valid BCD operands give the BCD results on both processors
+    for a in 0..100 {
+        for b in 0..100 {
+            for carry in [false, true] {
+                let sum = a + b + carry as u16;
+                let (a_bcd, b_bcd) = (to_bcd(a), to_bcd(b));
+                for add in [nmos_add, cmos_add] {
+                    let outcome = add(a_bcd, b_bcd, carry);
+                    assert!(outcome.value == to_bcd(sum % 100));
+                    assert!(outcome.carry == (sum >= 100));
+                }
+
+                let difference = (a + 100 - b - !carry as u16) % 100;
+                for sub in [nmos_sub, cmos_sub] {
+                    let outcome = sub(a_bcd, b_bcd, carry);
+                    assert!(outcome.value == to_bcd(difference));
+                    assert!(outcome.carry == (a >= b + !carry as u16));
+                }
+            }
+        }
+    }
+
+    // The NMOS flags don't follow the result, the CMOS ones do
+    let outcome = nmos_add(0x99, 0x01, false);
+    assert!(outcome.value == 0x00 && outcome.carry);
+    assert!(outcome.negative && !outcome.zero && !outcome.overflow);
+    let outcome = cmos_add(0x99, 0x01, false);
+    assert!(outcome.value == 0x00 && outcome.carry);
+    assert!(!outcome.negative && outcome.zero && !outcome.overflow);
+
+    let outcome = nmos_add(0x79, 0x00, true);
+    assert!(outcome.value == 0x80 && outcome.overflow && outcome.negative);
+
+    let outcome = nmos_sub(0x00, 0x01, true);
+    assert!(outcome.value == 0x99 && !outcome.carry && outcome.negative);
+
+    // Invalid BCD digits
+    assert!(nmos_add(0x0f, 0x00, false).value == 0x15);
+    assert!(nmos_add(0xff, 0xff, true).value == 0x55);
+    assert!(cmos_sub(0x00, 0x0f, true).value == 0x8b);
+
+    // All operands, as Bruce Clark's decimal test checks them
+    for a in 0..=u8::MAX {
+        for b in 0..=u8::MAX {
+            for carry in [false, true] {
+                assert!(nmos_add(a, b, carry) == clark_add(a, b, carry, false));
+                assert!(cmos_add(a, b, carry) == clark_add(a, b, carry, true));
+                assert!(nmos_sub(a, b, carry) == clark_sub(a, b, carry, false));
+                assert!(cmos_sub(a, b, carry) == clark_sub(a, b, carry, true));
+            }
+        }
+    }
+}
+
+/// Binary ADC: the result, the carry, the overflow and the negative flag
+fn binary_adc(a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool) {
+    let sum = a as u16 + b as u16 + carry as u16;
+    let value = sum as u8;
+    let overflow = (a ^ value) & (b ^ value) & 0x80 != 0;
+
+    (value, sum > 0xff, overflow, value & 0x80 != 0)
+}
+
+/// Binary SBC is ADC of the complement
+fn binary_sbc(a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool) {
+    binary_adc(a, !b, carry)
+}
+
+/// The ADD routine of Bruce Clark's decimal test, predicting ADC
+/// from the binary arithmetic on the digits
+fn clark_add(n1: u8, n2: u8, carry: bool, cmos: bool) -> BcdOutcome {
+    let n2h = [n2 & 0xf0, (n2 & 0xf0) + 0x0f];
+    let (al, ..) = binary_adc(n1 & 0x0f, n2 & 0x0f, carry);
+    let (al, x, c) = if al < 0x0a {
+        (al, 0, false)
+    } else {
+        (binary_adc(al, 0x05, true).0 & 0x0f, 1, true)
+    };
+    // The predicted N and V come from adding the high digits
+    let (ar, c, overflow, negative) = binary_adc(al | (n1 & 0xf0), n2h[x], c);
+    let (ar, c) = if c || ar >= 0xa0 {
+        (binary_adc(ar, 0x5f, true).0, true)
+    } else {
+        (ar, false)
+    };
+
+    let (binary, ..) = binary_adc(n1, n2, carry);
+    BcdOutcome {
+        value: ar,
+        carry: c,
+        overflow,
+        negative: if cmos { ar & 0x80 != 0 } else { negative },
+        zero: if cmos { ar == 0 } else { binary == 0 },
+    }
+}
 
-    let x = u8_to_bcd(89);
-    assert!(x == 0x89);
+/// The SUB1 (NMOS) and SUB2 (CMOS) routines of Bruce Clark's decimal test,
+/// predicting SBC from the binary arithmetic on the digits
+fn clark_sub(n1: u8, n2: u8, carry: bool, cmos: bool) -> BcdOutcome {
+    let n2h = [n2 & 0xf0, (n2 & 0xf0) + 0x0f];
+    let (al, no_borrow, ..) = binary_sbc(n1 & 0x0f, n2 & 0x0f, carry);
+    // The NMOS 6502 adjusts the low digit right away, the 65C02 at the end
+    let (al, x, c) = if no_borrow {
+        (al, 0, true)
+    } else if cmos {
+        (al & 0x0f, 1, false)
+    } else {
+        (binary_sbc(al, 0x05, false).0 & 0x0f, 1, false)
+    };
+    let (mut ar, no_borrow, ..) = binary_sbc(al | (n1 & 0xf0), n2h[x], c);
+    if !no_borrow {
+        ar = binary_sbc(ar, 0x5f, false).0;
+    }
+    if cmos && x != 0 {
+        ar = binary_sbc(ar, 0x06, true).0;
+    }
+
+    // The carry and V are the binary ones on both
+    let (binary, carry, overflow, negative) = binary_sbc(n1, n2, carry);
+    BcdOutcome {
+        value: ar,
+        carry,
+        overflow,
+        negative: if cmos { ar & 0x80 != 0 } else { negative },
+        zero: if cmos { ar == 0 } else { binary == 0 },
+    }
 }
@@ -0,0 +1,91 @@
+//! Bounded history of the `run` invocations to step back over
+//!
+//! Every record keeps the processor state before the invocation, and
+//! the old values of the memory cells it has written. When the history
+//! is full, the oldest record is dropped.
+
+use crate::snapshot::Snapshot;
+use crate::yamos6502::MAX_RUN_WRITES;
+
+/// State to step back to
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UndoRecord {
+    pub(crate) snapshot: Snapshot,
+    /// The written cells and their old values, in the order of writing
+    pub(crate) writes: [(u16, u8); MAX_RUN_WRITES],
+    pub(crate) len: usize,
+    /// All of the writes are recorded: no more than `MAX_RUN_WRITES`
+    /// of them, none made by the host directly, and all of the old values
+    /// peeked or read
+    pub(crate) exact: bool,
+}
+
+/// Ring buffer of the last `N` records. The old values are looked at with
+/// `Memory::peek`, and read with `Memory::read_access` where the memory
+/// can't be peeked, with the side effects of the read.
+#[derive(Debug, Clone)]
+pub struct UndoHistory<const N: usize> {
+    records: [Option<UndoRecord>; N],
+    /// Where the next record goes
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> Default for UndoHistory<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> UndoHistory<N> {
+    pub fn new() -> Self {
+        Self {
+            records: [None; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Number of the invocations that can be stepped back over
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The last invocation is recorded exactly, i.e. the old values of
+    /// the written cells could be peeked or read, and the host functions
+    /// have not written the memory more than can be recorded, or directly.
+    /// `false` if the history is empty.
+    pub fn can_step_back(&self) -> bool {
+        self.len != 0 && self.records[(self.next + N - 1) % N].is_some_and(|record| record.exact)
+    }
+
+    pub fn clear(&mut self) {
+        self.records = [None; N];
+        self.next = 0;
+        self.len = 0;
+    }
+
+    pub(crate) fn push(&mut self, record: UndoRecord) {
+        if N == 0 {
+            return;
+        }
+
+        self.records[self.next] = Some(record);
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<UndoRecord> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.next = (self.next + N - 1) % N;
+        self.len -= 1;
+        self.records[self.next].take()
+    }
+}
@@ -1,24 +1,62 @@
 //! Behavioral emulator of MOS 6502
 //!
+//! The NMOS 6502 is emulated by default, and the WDC 65C02, the Ricoh 2A03,
+//! the MOS 6510 or the MOS 6507 can be selected at construction (`CpuVariant`).
+//!
 //! There is no emulation of the microarch layer, e.g., no:
-//! * cycle-accurate emulation or cycle counting,
-//! * (unintended) support for "undocumented" instructions,
+//! * cycle-accurate emulation (the cycles are counted per instruction though),
+//! * support for the unstable "undocumented" instructions, and the stable ones
+//!   are supported only if allowed (`UndocumentedOpcodes::Allow`),
 //! * (unintended?) microarch side effects such as (not limited to):
-//!     * writing the old value first for the read-modify-write instructions,
-//!     * interrupt hijacking.
+//!     * writing the old value first for the read-modify-write instructions
+//!       unless the bus is stepped cycle by cycle (`BusMode::CycleStepped`),
+//!       then the memory is accessed as the NMOS 6502 does, including the
+//!       dummy reads and writes,
+//!     * interrupt hijacking and the interrupt latency, unless the interrupt
+//!       lines are sampled as the hardware does (`InterruptTiming::Hardware`).
+//!
+//! The indirect pointers are read across the page boundary unless the page
+//! wraparound is emulated as the hardware does (`PointerWrap::Hardware`).
 //!
 //! Unsuported instructions result in the execution jam, and the processor
-//! will roll its state back to the previous instruction.
+//! will roll its state back to the previous instruction, unless they are set
+//! to freeze it, to be skipped or to be handled by the host (`JamPolicy`).
 //!
 //! Stack underflow and overflow results in a fault, too, if configured.
+//! The writes the faulted instruction has made stay in the memory unless
+//! they are buffered until it retires (`WriteMode::Transactional`).
+//!
+//! The 65C02 can wait for an interrupt (WAI) or stop until reset (STP),
+//! `run` reports that instead of executing instructions.
 
 use core::fmt::Debug;
 use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU32;
 use core::sync::atomic::Ordering;
 
-use crate::bcd::bcd_to_u8;
-use crate::bcd::u8_to_bcd;
+use crate::bcd::cmos_add;
+use crate::bcd::cmos_sub;
+use crate::bcd::nmos_add;
+use crate::bcd::nmos_sub;
+use crate::bcd::BcdOutcome;
+use crate::insns::decode_65c02_insn;
+use crate::insns::decode_insn;
+use crate::insns::decode_undocumented_insn;
+use crate::insns::get_65c02_opcode_cycles;
+use crate::insns::get_opcode_cycles;
+use crate::insns::get_operand_bytes;
 use crate::insns::Insn;
+use crate::ioport::IoPort;
+use crate::ioport::IO_PORT_DATA;
+use crate::snapshot::Coded;
+use crate::snapshot::Snapshot;
+use crate::snapshot::SnapshotError;
+use crate::snapshot::SnapshotMemory;
+use crate::snapshot::SnapshotReader;
+use crate::snapshot::SnapshotWriter;
+use crate::snapshot::SNAPSHOT_SIZE;
+use crate::undo::UndoHistory;
+use crate::undo::UndoRecord;
 use crate::AddressMode;
 use crate::Register;
 use crate::RegisterFile;
@@ -42,10 +80,72 @@ impl core::fmt::Display for MemoryError {
 /// No more than 64 KiB of memory is supported
 pub const MAX_MEMORY_SIZE: usize = u16::MAX as usize + 1;
 
+/// What the processor accesses the bus for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Fetching the opcode, the SYNC pin is high
+    OpcodeFetch,
+    /// Fetching the bytes following the opcode: the operands and the trap
+    /// signature
+    OperandFetch,
+    /// Accessing the effective address or the indirect pointer
+    Data,
+    /// Pushing to or pulling from the stack
+    Stack,
+    /// Pulling the interrupt or the reset vector
+    Vector,
+    /// Discarded read or the unmodified value written back, made on
+    /// the cycles the processor is busy otherwise
+    Dummy,
+}
+
 /// A 16-bit addressable, 8-bit cell memory
 pub trait Memory {
     fn write(&mut self, addr: u16, value: u8) -> Result<(), MemoryError>;
     fn read(&mut self, addr: u16) -> Result<u8, MemoryError>;
+
+    /// Reads on behalf of the processor, telling what for. Implement
+    /// to decode e.g. SYNC or the vector pull, calls `read` by default.
+    fn read_access(&mut self, addr: u16, _kind: AccessKind) -> Result<u8, MemoryError> {
+        self.read(addr)
+    }
+
+    /// Writes on behalf of the processor, telling what for. Calls `write`
+    /// by default.
+    fn write_access(&mut self, addr: u16, value: u8, _kind: AccessKind) -> Result<(), MemoryError> {
+        self.write(addr, value)
+    }
+
+    /// Looks at the memory without the side effects a read might have,
+    /// e.g. clearing a status register, for the debuggers and the history.
+    /// `None` if the address can't be looked at, as by default: only
+    /// the memory itself knows which reads are free of side effects.
+    fn peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// Writes without the side effects a write might have, e.g. starting
+    /// a transfer, for the debuggers and the history. Calls `write`
+    /// by default, the devices with such effects should implement it.
+    fn poke(&mut self, addr: u16, value: u8) -> Result<(), MemoryError> {
+        self.write(addr, value)
+    }
+
+    /// Tells if the write would be refused, without making it. The buffered
+    /// writes (`WriteMode::Transactional`) are checked before any of them is
+    /// made. Allows any write by default.
+    fn check_write(&self, _addr: u16) -> Result<(), MemoryError> {
+        Ok(())
+    }
+
+    /// Cycles a device (e.g. a DMA controller or a video chip) holds RDY low
+    /// for, stalling the processor. Polled at every instruction boundary, and
+    /// with `BusMode::CycleStepped` before every read cycle, too, so the device
+    /// can halt the processor in the middle of the instruction. The NMOS 6502
+    /// doesn't halt on the write cycles, the 65C02 does.
+    fn stall_cycles(&mut self) -> u32 {
+        0
+    }
 }
 
 /// When an interrupt is signaled (hardware or the software via BRK),
@@ -63,9 +163,18 @@ pub const RESET_VECTOR: u16 = 0xFFFC;
 /// from a word at this address.
 pub const NMI_VECTOR: u16 = 0xFFFA;
 
+/// The address lines of the MOS 6507
+const MOS6507_ADDRESS_MASK: u16 = 0x1fff;
+
 /// Bottom of the stack
 pub const STACK_BOTTOM: u16 = 0x0100;
 
+/// Cycles taken to handle a reset
+pub const RESET_CYCLES: u8 = 7;
+
+/// Cycles taken to enter an interrupt handler (IRQ or NMI)
+pub const INTERRUPT_CYCLES: u8 = 7;
+
 /// Run normal exit
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RunExit {
@@ -75,6 +184,50 @@ pub enum RunExit {
     Interrupt,
     /// Non-maskable interrupt
     NonMaskableInterrupt,
+    /// Waiting for an interrupt after WAI, takes a cycle
+    Waiting,
+    /// Stopped by STP or jammed (`JamPolicy::Freeze`) until reset,
+    /// takes a cycle
+    Stopped,
+    /// Halted with RDY low for the cycles
+    Stalled(u32),
+    /// Serviced by the host trap, with the signature byte
+    Trapped(u8),
+}
+
+/// How long the batch runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// Retire the number of instructions
+    Instructions(u64),
+    /// Take at least the number of cycles, the last instruction
+    /// might go over
+    Cycles(u64),
+}
+
+/// Why the batch stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchExit {
+    /// The budget has run out
+    BudgetExhausted,
+    /// An instruction has retired with the program counter at
+    /// the address from the stop set
+    StopPc(u16),
+    /// Anything but an instruction retiring: an interrupt entry, waiting
+    /// for an interrupt, stopping or stalling
+    Event(RunExit),
+    /// The processor has faulted
+    Fault(RunError),
+}
+
+/// Outcome of the batch run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub exit: BatchExit,
+    /// Instructions retired, including the ones serviced by the traps
+    pub instructions: u64,
+    /// Cycles elapsed
+    pub cycles: u64,
 }
 
 /// Run error
@@ -90,6 +243,9 @@ pub enum RunError {
     StackUnderflow,
     /// Error occured when accessing the memory
     MemoryAccess(MemoryError),
+    /// The host functions made more writes than can be buffered
+    /// (`WriteMode::Transactional`)
+    TooManyWrites,
 }
 
 impl core::fmt::Display for RunError {
@@ -98,6 +254,24 @@ impl core::fmt::Display for RunError {
     }
 }
 
+/// Pin signaling error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinError {
+    /// The pin is not bonded out on this processor variant
+    NotBonded,
+    /// The IRQ source index is not below `IRQ_SOURCES`
+    NoSuchSource,
+}
+
+impl core::fmt::Display for PinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Number of the sources that can hold the IRQ line
+pub const IRQ_SOURCES: u8 = 32;
+
 /// Stack wraparound policy
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StackWraparound {
@@ -105,24 +279,629 @@ pub enum StackWraparound {
     Disallow,
 }
 
+/// Processor variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuVariant {
+    /// NMOS 6502
+    Nmos6502,
+    /// WDC 65C02, the CMOS version with a few new instructions and
+    /// addressing modes, and the NMOS bugs fixed
+    Wdc65C02,
+    /// Ricoh 2A03 of the NES, the NMOS 6502 without the decimal mode.
+    /// SED and CLD still change the D flag, yet ADC and SBC do binary
+    /// arithmetic
+    Ricoh2A03,
+    /// MOS 6510 of the C64, the NMOS 6502 with the I/O port at $0000
+    /// and $0001. These addresses never reach the memory
+    Mos6510,
+    /// MOS 6507 of the Atari 2600, the NMOS 6502 with only 13 address
+    /// lines, so the memory is mirrored every 8 KiB. The IRQ and NMI
+    /// pins are not bonded out
+    Mos6507,
+}
+
+/// Policy for the "undocumented" opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndocumentedOpcodes {
+    /// The stable ones are executed as the NMOS 6502 does
+    Allow,
+    /// All of them jam the processor
+    Disallow,
+}
+
+/// Reset sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetMode {
+    /// The program counter is loaded from the reset vector, and the stack
+    /// pointer is left as is
+    Simplified,
+    /// As the hardware does: an interrupt entry with the three stack pushes
+    /// suppressed, still decrementing the stack pointer by 3. At power-on,
+    /// the stack pointer is $00 before that, so it ends up at $FD
+    Hardware,
+}
+
+/// Page wraparound policy for reading the indirect pointers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerWrap {
+    /// The high byte of the pointer is read from the next address, even if
+    /// that is on the next page
+    CrossPage,
+    /// As the hardware does: the high byte of the `JMP ($xxFF)` pointer is
+    /// read from $xx00 on the NMOS 6502 (the 65C02 has that fixed), and the
+    /// zero page pointers at $FF wrap around to $00
+    Hardware,
+}
+
+/// When the interrupt lines are sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptTiming {
+    /// Before every instruction, and the I flag takes effect at once
+    Immediate,
+    /// As the hardware does: at the end of every instruction, so a signal
+    /// raised between the `run` calls is taken after the next instruction.
+    /// CLI, SEI and PLP change the I flag after the lines are sampled, which
+    /// delays an IRQ by one instruction after CLI and PLP, and lets one in
+    /// right after SEI. An NMI arriving during BRK or the IRQ entry takes
+    /// over the vector, and the B flag pushed by BRK stays set
+    Hardware,
+}
+
+/// Bus access mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusMode {
+    /// The memory is accessed only to carry out the instruction
+    Behavioral,
+    /// The memory is accessed on every cycle in the order the processor does,
+    /// including the dummy reads and writes
+    CycleStepped,
+}
+
+/// What the processor is busy with between the instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecState {
+    Running,
+    Waiting,
+    Stopped,
+}
+
+/// The host functions: the traps (`Mos6502::set_brk_trap` and
+/// `Mos6502::set_opcode_trap`) and the opcodes that jam the processor
+/// (`JamPolicy::Host`). The host keeps its own state, e.g. the open files
+/// of a syscall service. An error faults the processor and rolls it back
+/// as it does for an instruction.
+pub trait Host<M> {
+    /// Services the trap of the opcode instead of the 6502 code. Called with
+    /// the signature byte following the opcode, and the program counter past
+    /// it. Faults by default.
+    fn trap(
+        &mut self,
+        opcode: u8,
+        signature: u8,
+        bus: &mut HostBus<'_, M>,
+    ) -> Result<(), RunError> {
+        let _ = (signature, bus);
+        Err(RunError::InvalidInstruction(opcode))
+    }
+
+    /// Emulates the opcode that jams the processor. Called with the program
+    /// counter past the opcode. Returns the cycles the opcode takes, faults
+    /// by default.
+    fn jam(&mut self, opcode: u8, bus: &mut HostBus<'_, M>) -> Result<u8, RunError> {
+        let _ = bus;
+        Err(RunError::InvalidInstruction(opcode))
+    }
+}
+
+/// No host functions, the traps and the opcodes handed to the host fault
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoHost;
+
+impl<M> Host<M> for NoHost {}
+
+/// The processor as the host functions see it. `read_u8` and `write_u8`
+/// access the memory as the 6502 code would: through the on-chip I/O port
+/// of the MOS 6510 and the address lines of the MOS 6507, buffering the
+/// writes (`WriteMode::Transactional`) and recording them into the history.
+/// The memory itself is accessed directly.
+pub struct HostBus<'a, M> {
+    registers: &'a mut RegisterFile,
+    memory: &'a mut M,
+    io_port: &'a mut IoPort,
+    variant: CpuVariant,
+    writes: BusWrites<'a>,
+}
+
+impl<M> HostBus<'_, M>
+where
+    M: Memory,
+{
+    pub fn registers(&self) -> &RegisterFile {
+        self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut RegisterFile {
+        self.registers
+    }
+
+    pub fn memory(&self) -> &M {
+        self.memory
+    }
+
+    /// The memory itself. The writes made through it are neither buffered
+    /// nor recorded, so the history can't step back over the run.
+    pub fn memory_mut(&mut self) -> &mut M {
+        if self.writes.undo_log.active {
+            self.writes.undo_log.incomplete = true;
+        }
+        self.memory
+    }
+
+    pub fn read_u8(&mut self, addr: u16) -> Result<u8, RunError> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => Ok(self.io_port.read(addr)),
+            CpuVariant::Mos6507 => {
+                self.writes
+                    .read(self.memory, addr & MOS6507_ADDRESS_MASK, AccessKind::Data)
+            }
+            _ => self.writes.read(self.memory, addr, AccessKind::Data),
+        }
+        .map_err(RunError::MemoryAccess)
+    }
+
+    pub fn write_u8(&mut self, addr: u16, value: u8) -> Result<(), RunError> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => {
+                self.io_port.write(addr, value);
+                Ok(())
+            }
+            CpuVariant::Mos6507 => self.writes.write(
+                self.memory,
+                addr & MOS6507_ADDRESS_MASK,
+                value,
+                AccessKind::Data,
+            ),
+            _ => self
+                .writes
+                .write(self.memory, addr, value, AccessKind::Data),
+        }
+        .map_err(RunError::MemoryAccess)
+    }
+}
+
+/// Cycles the trap takes: the opcode and the signature byte are fetched
+const TRAP_CYCLES: u8 = 2;
+
+/// What the opcodes that jam the processor do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JamPolicy {
+    /// Fault with `RunError::InvalidInstruction` until reset
+    Fault,
+    /// Freeze as the hardware does, `run` reports `RunExit::Stopped` until reset
+    Freeze,
+    /// Skip the opcode and its operand as a NOP, without accessing the operand
+    Nop,
+    /// Call `Host::jam`
+    Host,
+}
+
+/// When the writes reach the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteMode {
+    /// As the instruction makes them
+    Immediate,
+    /// Buffered until the instruction or the interrupt entry retires, and
+    /// dropped if it faults, so the memory is left as it was before it. The
+    /// buffered writes are checked with `Memory::check_write` before any
+    /// of them is made. The reads see the buffered writes. The on-chip I/O
+    /// port of the MOS 6510 is written at once, and is rolled back with
+    /// the registers. The host functions share the buffer with the
+    /// instruction, and fault with `RunError::TooManyWrites` when it overflows
+    Transactional,
+}
+
+/// The most writes one `run` invocation buffers and records: the interrupt
+/// entry pushes three bytes, the instructions make no more than two, and
+/// the host functions have the rest
+pub(crate) const MAX_RUN_WRITES: usize = 16;
+
+/// Writes made by the instruction in progress
+#[derive(Debug, Clone, Copy)]
+struct WriteBuffer {
+    writes: [(u16, u8); MAX_RUN_WRITES],
+    kinds: [AccessKind; MAX_RUN_WRITES],
+    len: usize,
+    /// Writes were missed: dropped for the lack of room, made directly
+    /// by the host, or with the old value not to be peeked
+    incomplete: bool,
+    active: bool,
+}
+
+impl WriteBuffer {
+    fn new() -> Self {
+        Self {
+            writes: [(0, 0); MAX_RUN_WRITES],
+            kinds: [AccessKind::Data; MAX_RUN_WRITES],
+            len: 0,
+            incomplete: false,
+            active: false,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.incomplete = false;
+    }
+
+    fn push(&mut self, addr: u16, value: u8, kind: AccessKind) {
+        if self.len == MAX_RUN_WRITES {
+            self.incomplete = true;
+            return;
+        }
+
+        self.writes[self.len] = (addr, value);
+        self.kinds[self.len] = kind;
+        self.len += 1;
+    }
+
+    /// The latest value written to the address
+    fn find(&self, addr: u16) -> Option<u8> {
+        self.writes[..self.len]
+            .iter()
+            .rev()
+            .find(|&&(a, _)| a == addr)
+            .map(|&(_, value)| value)
+    }
+}
+
+/// Where the writes to the memory go besides it
+struct BusWrites<'a> {
+    buffer: &'a mut WriteBuffer,
+    undo_log: &'a mut WriteBuffer,
+}
+
+impl BusWrites<'_> {
+    /// Reads the memory, the writes buffered by the instruction
+    /// are seen first.
+    #[inline]
+    fn read<M: Memory>(&self, mem: &mut M, addr: u16, kind: AccessKind) -> Result<u8, MemoryError> {
+        if self.buffer.active {
+            if let Some(value) = self.buffer.find(addr) {
+                return Ok(value);
+            }
+        }
+
+        mem.read_access(addr, kind)
+    }
+
+    /// Writes the memory, or buffers the write until the instruction retires.
+    #[inline]
+    fn write<M: Memory>(
+        &mut self,
+        mem: &mut M,
+        addr: u16,
+        value: u8,
+        kind: AccessKind,
+    ) -> Result<(), MemoryError> {
+        if self.buffer.active {
+            self.buffer.push(addr, value, kind);
+            return Ok(());
+        }
+
+        self.write_through(mem, addr, value, kind)
+    }
+
+    /// Writes the memory, logging the old value when recording the history.
+    #[inline]
+    fn write_through<M: Memory>(
+        &mut self,
+        mem: &mut M,
+        addr: u16,
+        value: u8,
+        kind: AccessKind,
+    ) -> Result<(), MemoryError> {
+        let old = if self.undo_log.active {
+            // The memory that can't be looked at is read, side effects and all
+            match mem.peek(addr) {
+                Some(old) => Some(old),
+                None => mem.read_access(addr, AccessKind::Data).ok(),
+            }
+        } else {
+            None
+        };
+        mem.write_access(addr, value, kind)?;
+        if self.undo_log.active {
+            match old {
+                Some(old) => self.undo_log.push(addr, old, kind),
+                None => self.undo_log.incomplete = true,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Interrupt to enter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interrupt {
+    Nmi,
+    Irq,
+}
+
+impl Coded for StackWraparound {
+    const ALL: &'static [Self] = &[Self::Allow, Self::Disallow];
+}
+
+impl Coded for CpuVariant {
+    const ALL: &'static [Self] = &[
+        Self::Nmos6502,
+        Self::Wdc65C02,
+        Self::Ricoh2A03,
+        Self::Mos6510,
+        Self::Mos6507,
+    ];
+}
+
+impl Coded for UndocumentedOpcodes {
+    const ALL: &'static [Self] = &[Self::Allow, Self::Disallow];
+}
+
+impl Coded for ResetMode {
+    const ALL: &'static [Self] = &[Self::Simplified, Self::Hardware];
+}
+
+impl Coded for PointerWrap {
+    const ALL: &'static [Self] = &[Self::CrossPage, Self::Hardware];
+}
+
+impl Coded for InterruptTiming {
+    const ALL: &'static [Self] = &[Self::Immediate, Self::Hardware];
+}
+
+impl Coded for BusMode {
+    const ALL: &'static [Self] = &[Self::Behavioral, Self::CycleStepped];
+}
+
+impl Coded for ExecState {
+    const ALL: &'static [Self] = &[Self::Running, Self::Waiting, Self::Stopped];
+}
+
+impl Coded for WriteMode {
+    const ALL: &'static [Self] = &[Self::Immediate, Self::Transactional];
+}
+
+impl Coded for Option<Interrupt> {
+    const ALL: &'static [Self] = &[None, Some(Interrupt::Nmi), Some(Interrupt::Irq)];
+}
+
+/// The instruction sets the opcode handlers are specialized for
+const DECODER_NMOS: u8 = 0;
+const DECODER_NMOS_UNDOCUMENTED: u8 = 1;
+const DECODER_WDC_65C02: u8 = 2;
+
+const fn decode(decoder: u8, opcode: u8) -> Insn {
+    match decoder {
+        DECODER_NMOS => decode_insn(opcode),
+        DECODER_NMOS_UNDOCUMENTED => decode_undocumented_insn(opcode),
+        _ => decode_65c02_insn(opcode),
+    }
+}
+
+const fn opcode_cycles(decoder: u8, opcode: u8) -> u8 {
+    match decoder {
+        DECODER_WDC_65C02 => get_65c02_opcode_cycles(opcode),
+        _ => get_opcode_cycles(opcode),
+    }
+}
+
+/// Executes the instruction of one opcode
+type OpcodeHandler<M, H> = fn(&mut Mos6502<M, H>) -> Result<RunExit, RunError>;
+
+/// The handlers for all of the opcodes, indexed by the opcode
+macro_rules! opcode_handlers {
+    ($decoder:expr) => {
+        opcode_handlers!(@ $decoder;
+            0x00 0x01 0x02 0x03 0x04 0x05 0x06 0x07 0x08 0x09 0x0a 0x0b 0x0c 0x0d 0x0e 0x0f
+            0x10 0x11 0x12 0x13 0x14 0x15 0x16 0x17 0x18 0x19 0x1a 0x1b 0x1c 0x1d 0x1e 0x1f
+            0x20 0x21 0x22 0x23 0x24 0x25 0x26 0x27 0x28 0x29 0x2a 0x2b 0x2c 0x2d 0x2e 0x2f
+            0x30 0x31 0x32 0x33 0x34 0x35 0x36 0x37 0x38 0x39 0x3a 0x3b 0x3c 0x3d 0x3e 0x3f
+            0x40 0x41 0x42 0x43 0x44 0x45 0x46 0x47 0x48 0x49 0x4a 0x4b 0x4c 0x4d 0x4e 0x4f
+            0x50 0x51 0x52 0x53 0x54 0x55 0x56 0x57 0x58 0x59 0x5a 0x5b 0x5c 0x5d 0x5e 0x5f
+            0x60 0x61 0x62 0x63 0x64 0x65 0x66 0x67 0x68 0x69 0x6a 0x6b 0x6c 0x6d 0x6e 0x6f
+            0x70 0x71 0x72 0x73 0x74 0x75 0x76 0x77 0x78 0x79 0x7a 0x7b 0x7c 0x7d 0x7e 0x7f
+            0x80 0x81 0x82 0x83 0x84 0x85 0x86 0x87 0x88 0x89 0x8a 0x8b 0x8c 0x8d 0x8e 0x8f
+            0x90 0x91 0x92 0x93 0x94 0x95 0x96 0x97 0x98 0x99 0x9a 0x9b 0x9c 0x9d 0x9e 0x9f
+            0xa0 0xa1 0xa2 0xa3 0xa4 0xa5 0xa6 0xa7 0xa8 0xa9 0xaa 0xab 0xac 0xad 0xae 0xaf
+            0xb0 0xb1 0xb2 0xb3 0xb4 0xb5 0xb6 0xb7 0xb8 0xb9 0xba 0xbb 0xbc 0xbd 0xbe 0xbf
+            0xc0 0xc1 0xc2 0xc3 0xc4 0xc5 0xc6 0xc7 0xc8 0xc9 0xca 0xcb 0xcc 0xcd 0xce 0xcf
+            0xd0 0xd1 0xd2 0xd3 0xd4 0xd5 0xd6 0xd7 0xd8 0xd9 0xda 0xdb 0xdc 0xdd 0xde 0xdf
+            0xe0 0xe1 0xe2 0xe3 0xe4 0xe5 0xe6 0xe7 0xe8 0xe9 0xea 0xeb 0xec 0xed 0xee 0xef
+            0xf0 0xf1 0xf2 0xf3 0xf4 0xf5 0xf6 0xf7 0xf8 0xf9 0xfa 0xfb 0xfc 0xfd 0xfe 0xff
+        )
+    };
+    (@ $decoder:expr; $($opcode:literal)*) => {
+        [$(Self::execute_opcode::<{ $decoder }, $opcode>,)*]
+    };
+}
+
+/// The registers in the order they are saved in the snapshot
+const SNAPSHOT_REGISTERS: [Register; 5] = [
+    Register::A,
+    Register::X,
+    Register::Y,
+    Register::P,
+    Register::S,
+];
+
+fn save_fault(writer: &mut SnapshotWriter, fault: Option<RunError>) {
+    let (tag, opcode, memory_error) = match fault {
+        None => (0, 0, None),
+        Some(RunError::InvalidInstruction(opcode)) => (1, opcode, None),
+        Some(RunError::CannotFetchInstruction(e)) => (2, 0, Some(e)),
+        Some(RunError::StackOverflow) => (3, 0, None),
+        Some(RunError::StackUnderflow) => (4, 0, None),
+        Some(RunError::MemoryAccess(e)) => (5, 0, Some(e)),
+        Some(RunError::TooManyWrites) => (6, 0, None),
+    };
+    let (error_tag, addr) = match memory_error {
+        None => (0, 0),
+        Some(MemoryError::BadAddress(addr)) => (1, addr),
+        Some(MemoryError::ReadOnlyAddress(addr)) => (2, addr),
+    };
+
+    writer.u8(tag);
+    writer.u8(opcode);
+    writer.u8(error_tag);
+    writer.u16(addr);
+}
+
+fn restore_fault(reader: &mut SnapshotReader) -> Result<Option<RunError>, SnapshotError> {
+    let (tag, opcode, error_tag, addr) = (reader.u8()?, reader.u8()?, reader.u8()?, reader.u16()?);
+    let memory_error = match error_tag {
+        0 => None,
+        1 => Some(MemoryError::BadAddress(addr)),
+        2 => Some(MemoryError::ReadOnlyAddress(addr)),
+        _ => return Err(SnapshotError::Invalid),
+    };
+
+    Ok(match (tag, memory_error) {
+        (0, None) => None,
+        (1, None) => Some(RunError::InvalidInstruction(opcode)),
+        (2, Some(e)) => Some(RunError::CannotFetchInstruction(e)),
+        (3, None) => Some(RunError::StackOverflow),
+        (4, None) => Some(RunError::StackUnderflow),
+        (5, Some(e)) => Some(RunError::MemoryAccess(e)),
+        (6, None) => Some(RunError::TooManyWrites),
+        _ => return Err(SnapshotError::Invalid),
+    })
+}
+
+/// Processor state read from the snapshot, applied once all of it
+/// is valid
+struct SavedState {
+    reg_file: RegisterFile,
+    reset_pending: bool,
+    irq_line: u32,
+    nmi_line: bool,
+    nmi_pending: bool,
+    so_pending: bool,
+    rdy_low: bool,
+    stolen_cycles: u32,
+    fault: Option<RunError>,
+    last_opcode: u8,
+    allow_stack_wraparound: StackWraparound,
+    cycles: u64,
+    last_run_cycles: u64,
+    bus_mode: BusMode,
+    undocumented_opcodes: UndocumentedOpcodes,
+    pointer_wrap: PointerWrap,
+    reset_mode: ResetMode,
+    interrupt_timing: InterruptTiming,
+    write_mode: WriteMode,
+    power_on: bool,
+    variant: CpuVariant,
+    exec_state: ExecState,
+    polled_interrupt: Option<Interrupt>,
+    io_port: IoPort,
+}
+
+impl SavedState {
+    fn read(snapshot: &Snapshot) -> Result<Self, SnapshotError> {
+        let mut reader = snapshot.reader();
+
+        let mut reg_file = RegisterFile::default();
+        reg_file.set_pc(reader.u16()?);
+        for reg in SNAPSHOT_REGISTERS {
+            *reg_file.reg_mut(reg) = reader.u8()?;
+        }
+
+        Ok(Self {
+            reg_file,
+            reset_pending: reader.bool()?,
+            irq_line: reader.u32()?,
+            nmi_line: reader.bool()?,
+            nmi_pending: reader.bool()?,
+            so_pending: reader.bool()?,
+            rdy_low: reader.bool()?,
+            stolen_cycles: reader.u32()?,
+            fault: restore_fault(&mut reader)?,
+            last_opcode: reader.u8()?,
+            allow_stack_wraparound: reader.code()?,
+            cycles: reader.u64()?,
+            last_run_cycles: reader.u64()?,
+            bus_mode: reader.code()?,
+            undocumented_opcodes: reader.code()?,
+            pointer_wrap: reader.code()?,
+            reset_mode: reader.code()?,
+            interrupt_timing: reader.code()?,
+            write_mode: reader.code()?,
+            power_on: reader.bool()?,
+            variant: reader.code()?,
+            exec_state: reader.code()?,
+            polled_interrupt: reader.code()?,
+            io_port: IoPort::from_bytes(reader.bytes()?),
+        })
+    }
+}
+
+/// How the instruction accesses its operand in the memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OperandAccess {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
 /// Behavioral MOS 6502 emulator
 #[derive(Debug)]
-pub struct Mos6502<M>
+pub struct Mos6502<M, H = NoHost>
 where
     M: Memory,
 {
     mem: M,
+    host: H,
     reg_file: RegisterFile,
     // The target might not provide better options than
     // plain atomic store and loads. Should be a room
     // for perf optimization.
     reset_pending: AtomicBool,
-    irq_pending: AtomicBool,
+    // The wired-OR IRQ line, a bit per source holding it
+    irq_line: AtomicU32,
+    nmi_line: AtomicBool,
+    // The falling edge on the NMI line has been detected
     nmi_pending: AtomicBool,
+    // The falling edge on the SO pin has been detected
+    so_pending: AtomicBool,
+    rdy_low: AtomicBool,
+    // Cycles stolen by the host, e.g. for DMA
+    stolen_cycles: u32,
     // Jammed, only reset will help
     fault: Option<RunError>,
     last_opcode: u8,
     allow_stack_wraparound: StackWraparound,
+    // Cycles elapsed since the emulator was created
+    cycles: u64,
+    // Cycles taken by the last `run` invocation
+    last_run_cycles: u64,
+    bus_mode: BusMode,
+    undocumented_opcodes: UndocumentedOpcodes,
+    pointer_wrap: PointerWrap,
+    reset_mode: ResetMode,
+    interrupt_timing: InterruptTiming,
+    write_mode: WriteMode,
+    jam_policy: JamPolicy,
+    // The old values of the cells written, when recording the history
+    undo_log: WriteBuffer,
+    brk_trap: bool,
+    opcode_trap: Option<u8>,
+    write_buffer: WriteBuffer,
+    // Sampled at the end of the last instruction
+    polled_interrupt: Option<Interrupt>,
+    // No reset has been handled since power-on
+    power_on: bool,
+    variant: CpuVariant,
+    exec_state: ExecState,
+    io_port: IoPort,
 }
 
 impl<M> Mos6502<M>
@@ -130,45 +909,203 @@ where
     M: Memory,
 {
     pub fn new(memory: M, allow_stack_wraparound: StackWraparound) -> Self {
+        Self::with_variant(memory, CpuVariant::Nmos6502, allow_stack_wraparound)
+    }
+
+    pub fn with_variant(
+        memory: M,
+        variant: CpuVariant,
+        allow_stack_wraparound: StackWraparound,
+    ) -> Self {
+        Mos6502::with_host(memory, NoHost, variant, allow_stack_wraparound)
+    }
+
+    pub fn with_registers(
+        memory: M,
+        regf: RegisterFile,
+        allow_stack_wraparound: StackWraparound,
+    ) -> Self {
         Self {
             mem: memory,
-            reg_file: RegisterFile::default(),
+            host: NoHost,
+            reg_file: regf,
             reset_pending: AtomicBool::new(false),
+            irq_line: AtomicU32::new(0),
+            nmi_line: AtomicBool::new(false),
             nmi_pending: AtomicBool::new(false),
-            irq_pending: AtomicBool::new(false),
+            so_pending: AtomicBool::new(false),
+            rdy_low: AtomicBool::new(false),
+            stolen_cycles: 0,
             fault: None,
             last_opcode: 0,
             allow_stack_wraparound,
+            cycles: 0,
+            last_run_cycles: 0,
+            bus_mode: BusMode::Behavioral,
+            undocumented_opcodes: UndocumentedOpcodes::Disallow,
+            pointer_wrap: PointerWrap::CrossPage,
+            reset_mode: ResetMode::Simplified,
+            interrupt_timing: InterruptTiming::Immediate,
+            write_mode: WriteMode::Immediate,
+            jam_policy: JamPolicy::Fault,
+            undo_log: WriteBuffer::new(),
+            brk_trap: false,
+            opcode_trap: None,
+            write_buffer: WriteBuffer::new(),
+            polled_interrupt: None,
+            power_on: false,
+            variant: CpuVariant::Nmos6502,
+            exec_state: ExecState::Running,
+            io_port: IoPort::new(),
         }
     }
+}
 
-    pub fn with_registers(
+impl<M, H> Mos6502<M, H>
+where
+    M: Memory,
+    H: Host<M>,
+{
+    const NMOS_HANDLERS: [OpcodeHandler<M, H>; 256] = opcode_handlers!(DECODER_NMOS);
+    const NMOS_UNDOCUMENTED_HANDLERS: [OpcodeHandler<M, H>; 256] =
+        opcode_handlers!(DECODER_NMOS_UNDOCUMENTED);
+    const WDC_65C02_HANDLERS: [OpcodeHandler<M, H>; 256] = opcode_handlers!(DECODER_WDC_65C02);
+
+    /// Creates the processor calling the host functions
+    pub fn with_host(
         memory: M,
-        regf: RegisterFile,
+        host: H,
+        variant: CpuVariant,
         allow_stack_wraparound: StackWraparound,
     ) -> Self {
         Self {
             mem: memory,
-            reg_file: regf,
+            host,
+            reg_file: RegisterFile::default(),
             reset_pending: AtomicBool::new(false),
+            irq_line: AtomicU32::new(0),
+            nmi_line: AtomicBool::new(false),
             nmi_pending: AtomicBool::new(false),
-            irq_pending: AtomicBool::new(false),
+            so_pending: AtomicBool::new(false),
+            rdy_low: AtomicBool::new(false),
+            stolen_cycles: 0,
             fault: None,
             last_opcode: 0,
             allow_stack_wraparound,
+            cycles: 0,
+            last_run_cycles: 0,
+            bus_mode: BusMode::Behavioral,
+            undocumented_opcodes: UndocumentedOpcodes::Disallow,
+            pointer_wrap: PointerWrap::CrossPage,
+            reset_mode: ResetMode::Simplified,
+            interrupt_timing: InterruptTiming::Immediate,
+            write_mode: WriteMode::Immediate,
+            jam_policy: JamPolicy::Fault,
+            undo_log: WriteBuffer::new(),
+            brk_trap: false,
+            opcode_trap: None,
+            write_buffer: WriteBuffer::new(),
+            polled_interrupt: None,
+            power_on: true,
+            variant,
+            exec_state: ExecState::Running,
+            io_port: IoPort::new(),
         }
     }
 
-    pub fn set_irq_pending(&mut self) {
-        self.irq_pending.store(true, Ordering::Release);
+    /// Pulls the IRQ line low on behalf of the source. The line is held
+    /// until every source holding it has released it.
+    pub fn assert_irq(&mut self, source: u8) -> Result<(), PinError> {
+        let mask = self.irq_source_mask(source)?;
+        self.irq_line.fetch_or(mask, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    pub fn release_irq(&mut self, source: u8) -> Result<(), PinError> {
+        let mask = self.irq_source_mask(source)?;
+        self.irq_line.fetch_and(!mask, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    /// The sources holding the IRQ line, a bit per source.
+    pub fn irq_sources(&self) -> u32 {
+        self.irq_line.load(Ordering::Acquire)
+    }
+
+    fn irq_source_mask(&self, source: u8) -> Result<u32, PinError> {
+        if self.variant == CpuVariant::Mos6507 {
+            return Err(PinError::NotBonded);
+        }
+        if source >= IRQ_SOURCES {
+            return Err(PinError::NoSuchSource);
+        }
+
+        Ok(1 << source)
     }
 
     pub fn set_reset_pending(&mut self) {
         self.reset_pending.store(true, Ordering::Release);
     }
 
-    pub fn set_nmi_pending(&mut self) {
-        self.nmi_pending.store(true, Ordering::Release);
+    /// Pulls the NMI line low. The interrupt is taken once on the falling
+    /// edge, the line has to be released before the next one.
+    pub fn assert_nmi(&mut self) -> Result<(), PinError> {
+        if self.variant == CpuVariant::Mos6507 {
+            return Err(PinError::NotBonded);
+        }
+        if !self.nmi_line.swap(true, Ordering::AcqRel) {
+            self.nmi_pending.store(true, Ordering::Release);
+        }
+
+        Ok(())
+    }
+
+    pub fn release_nmi(&mut self) -> Result<(), PinError> {
+        if self.variant == CpuVariant::Mos6507 {
+            return Err(PinError::NotBonded);
+        }
+        self.nmi_line.store(false, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Pulses the SO (Set Overflow) pin. The V flag is set at the next
+    /// instruction boundary, before the instruction or the interrupt entry
+    /// that follows. The pin is not bonded out on the Ricoh 2A03, the MOS 6510
+    /// and the MOS 6507.
+    pub fn pulse_so(&mut self) -> Result<(), PinError> {
+        match self.variant {
+            CpuVariant::Nmos6502 | CpuVariant::Wdc65C02 => {
+                self.so_pending.store(true, Ordering::Release);
+
+                Ok(())
+            }
+            CpuVariant::Ricoh2A03 | CpuVariant::Mos6510 | CpuVariant::Mos6507 => {
+                Err(PinError::NotBonded)
+            }
+        }
+    }
+
+    /// Sets the level on the RDY pin. With RDY low, the processor halts on
+    /// the next read cycle, that is the opcode fetch, and every `run` takes
+    /// a cycle until RDY is high again. The host can't change the pin while
+    /// the instruction runs; the devices halting the processor on the read
+    /// cycles in the middle of it return the cycles from `Memory::stall_cycles`
+    /// with `BusMode::CycleStepped`.
+    pub fn set_rdy(&mut self, ready: bool) {
+        self.rdy_low.store(!ready, Ordering::Release);
+    }
+
+    pub fn rdy(&self) -> bool {
+        !self.rdy_low.load(Ordering::Acquire)
+    }
+
+    /// Holds RDY low for the cycles, e.g. 513 or 514 for the sprite DMA of
+    /// the Ricoh 2A03. The next `run` reports them as stolen.
+    pub fn steal_cycles(&mut self, cycles: u32) {
+        self.stolen_cycles = self.stolen_cycles.saturating_add(cycles);
     }
 
     pub fn registers(&self) -> &RegisterFile {
@@ -179,38 +1116,510 @@ where
         &mut self.reg_file
     }
 
+    /// Sets the bus access mode. The default is `BusMode::Behavioral`.
+    pub fn set_bus_mode(&mut self, bus_mode: BusMode) {
+        self.bus_mode = bus_mode;
+    }
+
+    pub fn bus_mode(&self) -> BusMode {
+        self.bus_mode
+    }
+
+    /// Sets the policy for the "undocumented" opcodes. The default is
+    /// `UndocumentedOpcodes::Disallow`. The 65C02 has no "undocumented"
+    /// instructions, its undefined opcodes are always NOPs.
+    pub fn set_undocumented_opcodes(&mut self, undocumented_opcodes: UndocumentedOpcodes) {
+        self.undocumented_opcodes = undocumented_opcodes;
+    }
+
+    pub fn undocumented_opcodes(&self) -> UndocumentedOpcodes {
+        self.undocumented_opcodes
+    }
+
+    /// Sets the page wraparound policy for reading the indirect pointers.
+    /// The default is `PointerWrap::CrossPage`.
+    pub fn set_pointer_wrap(&mut self, pointer_wrap: PointerWrap) {
+        self.pointer_wrap = pointer_wrap;
+    }
+
+    pub fn pointer_wrap(&self) -> PointerWrap {
+        self.pointer_wrap
+    }
+
+    /// Sets the reset sequence. The default is `ResetMode::Simplified`.
+    pub fn set_reset_mode(&mut self, reset_mode: ResetMode) {
+        self.reset_mode = reset_mode;
+    }
+
+    pub fn reset_mode(&self) -> ResetMode {
+        self.reset_mode
+    }
+
+    /// Sets when the interrupt lines are sampled. The default is
+    /// `InterruptTiming::Immediate`.
+    pub fn set_interrupt_timing(&mut self, interrupt_timing: InterruptTiming) {
+        self.interrupt_timing = interrupt_timing;
+    }
+
+    pub fn interrupt_timing(&self) -> InterruptTiming {
+        self.interrupt_timing
+    }
+
+    /// Sets when the writes reach the memory. The default is
+    /// `WriteMode::Immediate`.
+    pub fn set_write_mode(&mut self, write_mode: WriteMode) {
+        self.write_mode = write_mode;
+    }
+
+    pub fn write_mode(&self) -> WriteMode {
+        self.write_mode
+    }
+
+    /// Sets what the opcodes that jam the processor do. The default is
+    /// `JamPolicy::Fault`.
+    pub fn set_jam_policy(&mut self, jam_policy: JamPolicy) {
+        self.jam_policy = jam_policy;
+    }
+
+    pub fn jam_policy(&self) -> JamPolicy {
+        self.jam_policy
+    }
+
+    /// Traps `BRK #n` into `Host::trap` instead of vectoring through
+    /// `IRQ_BRK_VECTOR`.
+    pub fn set_brk_trap(&mut self, brk_trap: bool) {
+        self.brk_trap = brk_trap;
+    }
+
+    pub fn brk_trap(&self) -> bool {
+        self.brk_trap
+    }
+
+    /// Traps the opcode followed by the signature byte into `Host::trap`,
+    /// typically one of the reserved opcodes. It takes precedence over
+    /// the instruction, if any.
+    pub fn set_opcode_trap(&mut self, opcode: Option<u8>) {
+        self.opcode_trap = opcode;
+    }
+
+    pub fn opcode_trap(&self) -> Option<u8> {
+        self.opcode_trap
+    }
+
+    pub fn host(&self) -> &H {
+        &self.host
+    }
+
+    pub fn host_mut(&mut self) -> &mut H {
+        &mut self.host
+    }
+
+    pub fn variant(&self) -> CpuVariant {
+        self.variant
+    }
+
+    /// Cycles elapsed since the emulator was created.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Cycles taken by the last invocation of `run`: the retired
+    /// instruction, the interrupt entry or the stall, plus the reset
+    /// sequence if a reset was handled.
+    pub fn last_run_cycles(&self) -> u64 {
+        self.last_run_cycles
+    }
+
+    pub fn memory(&self) -> &M {
+        &self.mem
+    }
+
+    pub fn memory_mut(&mut self) -> &mut M {
+        &mut self.mem
+    }
+
+    /// Saves the processor state. The JAM policy and the traps are set up
+    /// by the host, and are not saved.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::new();
+        let mut writer = snapshot.writer();
+
+        writer.u16(self.reg_file.pc());
+        for reg in SNAPSHOT_REGISTERS {
+            writer.u8(self.reg_file.reg(reg));
+        }
+        writer.bool(self.reset_pending.load(Ordering::Acquire));
+        writer.u32(self.irq_line.load(Ordering::Acquire));
+        writer.bool(self.nmi_line.load(Ordering::Acquire));
+        writer.bool(self.nmi_pending.load(Ordering::Acquire));
+        writer.bool(self.so_pending.load(Ordering::Acquire));
+        writer.bool(self.rdy_low.load(Ordering::Acquire));
+        writer.u32(self.stolen_cycles);
+        save_fault(&mut writer, self.fault);
+        writer.u8(self.last_opcode);
+        writer.code(self.allow_stack_wraparound);
+        writer.u64(self.cycles);
+        writer.u64(self.last_run_cycles);
+        writer.code(self.bus_mode);
+        writer.code(self.undocumented_opcodes);
+        writer.code(self.pointer_wrap);
+        writer.code(self.reset_mode);
+        writer.code(self.interrupt_timing);
+        writer.code(self.write_mode);
+        writer.bool(self.power_on);
+        writer.code(self.variant);
+        writer.code(self.exec_state);
+        writer.code(self.polled_interrupt);
+        writer.bytes(&self.io_port.to_bytes());
+        writer.finish();
+
+        snapshot
+    }
+
+    /// Restores the processor state saved by `snapshot`, possibly into
+    /// another instance. Nothing is changed if the snapshot is not valid.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let state = SavedState::read(snapshot)?;
+        self.apply_state(state);
+
+        Ok(())
+    }
+
+    fn apply_state(&mut self, state: SavedState) {
+        self.reg_file = state.reg_file;
+        self.reset_pending
+            .store(state.reset_pending, Ordering::Release);
+        self.irq_line.store(state.irq_line, Ordering::Release);
+        self.nmi_line.store(state.nmi_line, Ordering::Release);
+        self.nmi_pending.store(state.nmi_pending, Ordering::Release);
+        self.so_pending.store(state.so_pending, Ordering::Release);
+        self.rdy_low.store(state.rdy_low, Ordering::Release);
+        self.stolen_cycles = state.stolen_cycles;
+        self.fault = state.fault;
+        self.last_opcode = state.last_opcode;
+        self.allow_stack_wraparound = state.allow_stack_wraparound;
+        self.cycles = state.cycles;
+        self.last_run_cycles = state.last_run_cycles;
+        self.bus_mode = state.bus_mode;
+        self.undocumented_opcodes = state.undocumented_opcodes;
+        self.pointer_wrap = state.pointer_wrap;
+        self.reset_mode = state.reset_mode;
+        self.interrupt_timing = state.interrupt_timing;
+        self.write_mode = state.write_mode;
+        self.write_buffer = WriteBuffer::new();
+        self.power_on = state.power_on;
+        self.variant = state.variant;
+        self.exec_state = state.exec_state;
+        self.polled_interrupt = state.polled_interrupt;
+        self.io_port = state.io_port;
+    }
+
+    /// The I/O port of the MOS 6510, `None` for the other variants.
+    pub fn io_port(&self) -> Option<&IoPort> {
+        match self.variant {
+            CpuVariant::Mos6510 => Some(&self.io_port),
+            _ => None,
+        }
+    }
+
+    /// The I/O port of the MOS 6510 to drive the input pins, `None` for
+    /// the other variants.
+    pub fn io_port_mut(&mut self) -> Option<&mut IoPort> {
+        match self.variant {
+            CpuVariant::Mos6510 => Some(&mut self.io_port),
+            _ => None,
+        }
+    }
+
+    /// Halts the processor for the cycles a device holds RDY low for
+    /// in the middle of the instruction.
+    fn wait_ready(&mut self, write: bool) {
+        if !write || self.variant == CpuVariant::Wdc65C02 {
+            self.cycles += self.mem.stall_cycles() as u64;
+        }
+    }
+
+    /// All reads of the processor go through here. With `BusMode::CycleStepped`,
+    /// a device might halt it before the read cycle.
+    #[inline]
+    fn bus_read(&mut self, addr: u16, kind: AccessKind) -> Result<u8, MemoryError> {
+        if self.bus_mode == BusMode::CycleStepped {
+            self.wait_ready(false);
+        }
+
+        self.port_read(addr, kind)
+    }
+
+    /// The on-chip I/O port is accessed instead of the memory for its
+    /// addresses, and the address lines not bonded out are dropped.
+    #[inline]
+    fn port_read(&mut self, addr: u16, kind: AccessKind) -> Result<u8, MemoryError> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => Ok(self.io_port.read(addr)),
+            CpuVariant::Mos6507 => self.mem_read(addr & MOS6507_ADDRESS_MASK, kind),
+            _ => self.mem_read(addr, kind),
+        }
+    }
+
+    /// All writes of the processor go through here. With `BusMode::CycleStepped`,
+    /// a device might halt the 65C02 before the write cycle.
+    #[inline]
+    fn bus_write(&mut self, addr: u16, value: u8, kind: AccessKind) -> Result<(), MemoryError> {
+        if self.bus_mode == BusMode::CycleStepped {
+            self.wait_ready(true);
+        }
+
+        self.port_write(addr, value, kind)
+    }
+
+    /// The on-chip I/O port is accessed instead of the memory for its
+    /// addresses, and the address lines not bonded out are dropped.
+    #[inline]
+    fn port_write(&mut self, addr: u16, value: u8, kind: AccessKind) -> Result<(), MemoryError> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => {
+                self.io_port.write(addr, value);
+                Ok(())
+            }
+            CpuVariant::Mos6507 => self.mem_write(addr & MOS6507_ADDRESS_MASK, value, kind),
+            _ => self.mem_write(addr, value, kind),
+        }
+    }
+
+    /// The memory with the writes made to it
+    #[inline]
+    fn split_bus(&mut self) -> (&mut M, BusWrites<'_>) {
+        (
+            &mut self.mem,
+            BusWrites {
+                buffer: &mut self.write_buffer,
+                undo_log: &mut self.undo_log,
+            },
+        )
+    }
+
+    #[inline]
+    fn mem_read(&mut self, addr: u16, kind: AccessKind) -> Result<u8, MemoryError> {
+        let (mem, writes) = self.split_bus();
+        writes.read(mem, addr, kind)
+    }
+
+    #[inline]
+    fn mem_write(&mut self, addr: u16, value: u8, kind: AccessKind) -> Result<(), MemoryError> {
+        let (mem, mut writes) = self.split_bus();
+        writes.write(mem, addr, value, kind)
+    }
+
+    /// Writes the buffered writes to the memory in order, if the memory
+    /// accepts all of them. Should it refuse one after accepting the check,
+    /// the ones before it have been made already.
+    fn commit_writes(&mut self) -> Result<(), RunError> {
+        let len = self.write_buffer.len;
+        let incomplete = self.write_buffer.incomplete;
+        self.write_buffer.clear();
+        if incomplete {
+            return Err(RunError::TooManyWrites);
+        }
+        let writes = self.write_buffer.writes;
+        let kinds = self.write_buffer.kinds;
+        for &(addr, _) in &writes[..len] {
+            self.mem.check_write(addr).map_err(RunError::MemoryAccess)?;
+        }
+        let (mem, mut bus_writes) = self.split_bus();
+        for (&(addr, value), &kind) in writes[..len].iter().zip(&kinds) {
+            bus_writes
+                .write_through(mem, addr, value, kind)
+                .map_err(RunError::MemoryAccess)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads as the processor would, between the instructions: the devices
+    /// don't get to halt the processor.
     pub fn read_u8(&mut self, addr: u16) -> Result<u8, RunError> {
-        self.mem.read(addr).map_err(RunError::MemoryAccess)
+        self.port_read(addr, AccessKind::Data)
+            .map_err(RunError::MemoryAccess)
     }
 
+    /// Writes as the processor would, between the instructions.
     pub fn write_u8(&mut self, addr: u16, value: u8) -> Result<(), RunError> {
-        self.mem.write(addr, value).map_err(RunError::MemoryAccess)
+        self.port_write(addr, value, AccessKind::Data)
+            .map_err(RunError::MemoryAccess)
     }
 
     pub fn read_u16(&mut self, addr: u16) -> Result<u16, RunError> {
-        let lo = self.mem.read(addr).map_err(RunError::MemoryAccess)?;
-        let hi = self
-            .mem
-            .read(addr.wrapping_add(1))
-            .map_err(RunError::MemoryAccess)?;
+        let lo = self.read_u8(addr)?;
+        let hi = self.read_u8(addr.wrapping_add(1))?;
 
         Ok(u16::from_le_bytes([lo, hi]))
     }
 
     pub fn write_u16(&mut self, addr: u16, value: u16) -> Result<(), RunError> {
-        self.mem
-            .write(addr, value as u8)
-            .map_err(RunError::MemoryAccess)?;
-        self.mem
-            .write(addr.wrapping_add(1), (value >> 8) as u8)
-            .map_err(RunError::MemoryAccess)?;
+        self.write_u8(addr, value as u8)?;
+        self.write_u8(addr.wrapping_add(1), (value >> 8) as u8)?;
+
+        Ok(())
+    }
+
+    /// Looks at the memory as the processor would see it, without
+    /// the side effects of a read. `None` if `Memory::peek` is.
+    pub fn peek_u8(&self, addr: u16) -> Option<u8> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => Some(self.io_port.read(addr)),
+            CpuVariant::Mos6507 => self.mem.peek(addr & MOS6507_ADDRESS_MASK),
+            _ => self.mem.peek(addr),
+        }
+    }
+
+    /// Writes as the processor would, without the side effects of a write.
+    /// The write is not recorded into the history.
+    pub fn poke_u8(&mut self, addr: u16, value: u8) -> Result<(), RunError> {
+        match self.variant {
+            CpuVariant::Mos6510 if addr <= IO_PORT_DATA => {
+                self.io_port.write(addr, value);
+                Ok(())
+            }
+            CpuVariant::Mos6507 => self.mem.poke(addr & MOS6507_ADDRESS_MASK, value),
+            _ => self.mem.poke(addr, value),
+        }
+        .map_err(RunError::MemoryAccess)
+    }
+
+    pub fn peek_u16(&self, addr: u16) -> Option<u16> {
+        let lo = self.peek_u8(addr)?;
+        let hi = self.peek_u8(addr.wrapping_add(1))?;
+
+        Some(u16::from_le_bytes([lo, hi]))
+    }
+
+    pub fn poke_u16(&mut self, addr: u16, value: u16) -> Result<(), RunError> {
+        self.poke_u8(addr, value as u8)?;
+        self.poke_u8(addr.wrapping_add(1), (value >> 8) as u8)?;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn read_access(&mut self, addr: u16, kind: AccessKind) -> Result<u8, RunError> {
+        self.bus_read(addr, kind).map_err(RunError::MemoryAccess)
+    }
+
+    #[inline]
+    fn write_access(&mut self, addr: u16, value: u8, kind: AccessKind) -> Result<(), RunError> {
+        self.bus_write(addr, value, kind)
+            .map_err(RunError::MemoryAccess)
+    }
+
+    /// Reads the data for the instruction
+    #[inline]
+    fn read_data(&mut self, addr: u16) -> Result<u8, RunError> {
+        self.read_access(addr, AccessKind::Data)
+    }
+
+    #[inline]
+    fn read_data_u16(&mut self, addr: u16) -> Result<u16, RunError> {
+        let lo = self.read_data(addr)?;
+        let hi = self.read_data(addr.wrapping_add(1))?;
+
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    /// Writes the data for the instruction
+    #[inline]
+    fn write_data(&mut self, addr: u16, value: u8) -> Result<(), RunError> {
+        self.write_access(addr, value, AccessKind::Data)
+    }
+
+    /// Fetches the byte following the opcode
+    #[inline]
+    fn fetch_u8(&mut self, addr: u16) -> Result<u8, RunError> {
+        self.read_access(addr, AccessKind::OperandFetch)
+    }
+
+    /// Fetches the two bytes following the opcode
+    #[inline]
+    fn fetch_u16(&mut self, addr: u16) -> Result<u16, RunError> {
+        let lo = self.fetch_u8(addr)?;
+        let hi = self.fetch_u8(addr.wrapping_add(1))?;
+
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    /// Pulls the interrupt or the reset vector
+    #[inline]
+    fn read_vector(&mut self, vector: u16) -> Result<u16, RunError> {
+        let lo = self.read_access(vector, AccessKind::Vector)?;
+        let hi = self.read_access(vector.wrapping_add(1), AccessKind::Vector)?;
+
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    /// Reads an indirect pointer. The high byte is read from the same page
+    /// if the page wraparound is emulated.
+    #[inline]
+    fn read_pointer(&mut self, addr: u16, wrap_in_page: bool) -> Result<u16, RunError> {
+        if !wrap_in_page || self.pointer_wrap == PointerWrap::CrossPage {
+            return self.read_data_u16(addr);
+        }
+
+        let lo = self.read_data(addr)?;
+        let hi = self.read_data((addr & 0xff00) | (addr.wrapping_add(1) & 0x00ff))?;
+
+        Ok(u16::from_le_bytes([lo, hi]))
+    }
+
+    /// Reads a byte only when stepping the bus cycle by cycle. The value
+    /// is discarded, yet the memory might have side effects on reads.
+    #[inline]
+    fn dummy_read(&mut self, addr: u16) -> Result<(), RunError> {
+        if self.bus_mode == BusMode::CycleStepped {
+            self.read_access(addr, AccessKind::Dummy)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles the index carry into the high byte of the effective address.
+    /// The processor reads from the address with the high byte not fixed yet,
+    /// and that read is discarded if the page boundary is crossed, costing
+    /// an extra cycle. Writes always take that extra cycle. The 65C02 reads
+    /// the last byte of the instruction again instead.
+    #[inline]
+    fn fix_indexed_address(
+        &mut self,
+        base: u16,
+        ea: u16,
+        access: OperandAccess,
+    ) -> Result<(), RunError> {
+        let page_crossed = (base ^ ea) & 0xff00 != 0;
+        if page_crossed || access != OperandAccess::Read {
+            let addr = match self.variant {
+                CpuVariant::Wdc65C02 => self.reg_file.pc().wrapping_sub(1),
+                CpuVariant::Nmos6502
+                | CpuVariant::Ricoh2A03
+                | CpuVariant::Mos6510
+                | CpuVariant::Mos6507 => (base & 0xff00) | (ea & 0x00ff),
+            };
+            self.dummy_read(addr)?;
+        }
+        if page_crossed && access == OperandAccess::Read {
+            self.cycles += 1;
+        }
 
         Ok(())
     }
 
     /// Computes the effective address. Expects the program counter being advanced past
     /// the instruction opcode, and advances it to skip the addressing mode bytes.
-    fn get_effective_address(&mut self, addr_mode: AddressMode) -> Result<u16, RunError> {
+    /// Inlined into the operand helpers in the release builds, as almost every
+    /// instruction goes through it.
+    #[cfg_attr(not(debug_assertions), inline(always))]
+    fn get_effective_address(
+        &mut self,
+        addr_mode: AddressMode,
+        access: OperandAccess,
+    ) -> Result<u16, RunError> {
         match addr_mode {
             AddressMode::Immediate | AddressMode::Relative => {
                 let ea = self.reg_file.pc();
@@ -219,72 +1628,98 @@ where
                 Ok(ea)
             }
             AddressMode::Indirect => {
-                let ptr = self.read_u16(self.reg_file.pc())?;
+                let ptr = self.fetch_u16(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(2);
-                let ea = self.read_u16(ptr)?;
+                // The 65C02 takes an extra cycle to carry into the high byte
+                // of the pointer, the NMOS 6502 doesn't carry
+                let cmos = self.variant == CpuVariant::Wdc65C02;
+                if cmos {
+                    self.dummy_read(self.reg_file.pc().wrapping_sub(1))?;
+                }
+                let ea = self.read_pointer(ptr, !cmos)?;
+
+                Ok(ea)
+            }
+            AddressMode::AbsoluteXindirect => {
+                let base = self.fetch_u16(self.reg_file.pc())?;
+                self.reg_file.adjust_pc_by(2);
+                self.dummy_read(self.reg_file.pc().wrapping_sub(1))?;
+                let ea = self.read_data_u16(base.wrapping_add(self.reg_file.x().into()))?;
+
+                Ok(ea)
+            }
+            AddressMode::ZeropageRelative => {
+                // The branch offset is left for the branch to fetch
+                let ea = self.fetch_u8(self.reg_file.pc())?.into();
+                self.reg_file.adjust_pc_by(1);
+
+                Ok(ea)
+            }
+            AddressMode::ZeropageIndirect => {
+                let ptr = self.fetch_u8(self.reg_file.pc())?.into();
+                self.reg_file.adjust_pc_by(1);
+                let ea = self.read_pointer(ptr, true)?;
 
                 Ok(ea)
             }
             AddressMode::Xindirect => {
-                let ptr = self
-                    .read_u8(self.reg_file.pc())?
-                    .wrapping_add(self.reg_file.x())
-                    .into();
+                let ptr = self.fetch_u8(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(1);
-                let ea = self.read_u16(ptr)?;
+                self.dummy_read(ptr.into())?;
+                let ea = self.read_pointer(ptr.wrapping_add(self.reg_file.x()).into(), true)?;
 
                 Ok(ea)
             }
             AddressMode::IndirectY => {
-                let ptr = self.read_u8(self.reg_file.pc())?.into();
+                let ptr = self.fetch_u8(self.reg_file.pc())?.into();
                 self.reg_file.adjust_pc_by(1);
-                let ea = self.read_u16(ptr)?.wrapping_add(self.reg_file.y().into());
+                let base = self.read_pointer(ptr, true)?;
+                let ea = base.wrapping_add(self.reg_file.y().into());
+                self.fix_indexed_address(base, ea, access)?;
 
                 Ok(ea)
             }
             AddressMode::Absolute => {
-                let ea = self.read_u16(self.reg_file.pc())?;
+                let ea = self.fetch_u16(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(2);
 
                 Ok(ea)
             }
             AddressMode::AbsoluteX => {
-                let ea = self
-                    .read_u16(self.reg_file.pc())?
-                    .wrapping_add(self.reg_file.x().into());
+                let base = self.fetch_u16(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(2);
+                let ea = base.wrapping_add(self.reg_file.x().into());
+                self.fix_indexed_address(base, ea, access)?;
 
                 Ok(ea)
             }
             AddressMode::AbsoluteY => {
-                let ea = self
-                    .read_u16(self.reg_file.pc())?
-                    .wrapping_add(self.reg_file.y().into());
+                let base = self.fetch_u16(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(2);
+                let ea = base.wrapping_add(self.reg_file.y().into());
+                self.fix_indexed_address(base, ea, access)?;
 
                 Ok(ea)
             }
             AddressMode::Zeropage => {
-                let ea = self.read_u8(self.reg_file.pc())?.into();
+                let ea = self.fetch_u8(self.reg_file.pc())?.into();
                 self.reg_file.adjust_pc_by(1);
 
                 Ok(ea)
             }
             AddressMode::ZeropageX => {
-                let ea = self
-                    .read_u8(self.reg_file.pc())?
-                    .wrapping_add(self.reg_file.x())
-                    .into();
+                let base = self.fetch_u8(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(1);
+                self.dummy_read(base.into())?;
+                let ea = base.wrapping_add(self.reg_file.x()).into();
 
                 Ok(ea)
             }
             AddressMode::ZeropageY => {
-                let ea = self
-                    .read_u8(self.reg_file.pc())?
-                    .wrapping_add(self.reg_file.y())
-                    .into();
+                let base = self.fetch_u8(self.reg_file.pc())?;
                 self.reg_file.adjust_pc_by(1);
+                self.dummy_read(base.into())?;
+                let ea = base.wrapping_add(self.reg_file.y()).into();
 
                 Ok(ea)
             }
@@ -298,6 +1733,21 @@ where
             .set_flag_from_cond(Status::Negative, (data as i8) < 0);
     }
 
+    #[inline]
+    fn read_operand(&mut self, addr_mode: AddressMode) -> Result<u8, RunError> {
+        let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+        self.read_operand_at(ea, addr_mode)
+    }
+
+    /// The immediate operand is fetched along with the instruction
+    #[inline]
+    fn read_operand_at(&mut self, ea: u16, addr_mode: AddressMode) -> Result<u8, RunError> {
+        match addr_mode {
+            AddressMode::Immediate => self.fetch_u8(ea),
+            _ => self.read_data(ea),
+        }
+    }
+
     #[inline]
     fn read_modify_to_reg<F>(
         &mut self,
@@ -308,8 +1758,7 @@ where
     where
         F: FnMut(u8) -> u8,
     {
-        let ea = self.get_effective_address(addr_mode)?;
-        let value = self.read_u8(ea)?;
+        let value = self.read_operand(addr_mode)?;
         let value = modify(value);
         *self.reg_file.reg_mut(reg) = value;
         self.update_flags_nz(value);
@@ -326,9 +1775,9 @@ where
 
     #[inline]
     fn reg_to_mem(&mut self, reg: Register, addr_mode: AddressMode) -> Result<(), RunError> {
-        let ea = self.get_effective_address(addr_mode)?;
+        let ea = self.get_effective_address(addr_mode, OperandAccess::Write)?;
         let value = self.reg_file.reg(reg);
-        self.write_u8(ea, value)?;
+        self.write_data(ea, value)?;
 
         Ok(())
     }
@@ -340,20 +1789,78 @@ where
         self.update_flags_nz(value);
     }
 
+    /// While modifying the value, the NMOS 6502 writes the unmodified value
+    /// back, and the 65C02 reads it once more.
+    #[inline]
+    fn modify_dummy_access(&mut self, ea: u16, value: u8) -> Result<(), RunError> {
+        if self.bus_mode == BusMode::CycleStepped {
+            match self.variant {
+                CpuVariant::Wdc65C02 => _ = self.read_access(ea, AccessKind::Dummy)?,
+                CpuVariant::Nmos6502
+                | CpuVariant::Ricoh2A03
+                | CpuVariant::Mos6510
+                | CpuVariant::Mos6507 => self.write_access(ea, value, AccessKind::Dummy)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn read_modify_write_mem<F>(
+        &mut self,
+        addr_mode: AddressMode,
+        access: OperandAccess,
+        mut modify: F,
+    ) -> Result<u8, RunError>
+    where
+        F: FnMut(u8) -> u8,
+    {
+        let ea = self.get_effective_address(addr_mode, access)?;
+        let value = self.read_data(ea)?;
+        self.modify_dummy_access(ea, value)?;
+        let value = modify(value);
+        self.write_data(ea, value)?;
+        self.update_flags_nz(value);
+
+        Ok(value)
+    }
+
+    /// The 65C02 fixes up the index for the shifts and rotations only
+    /// if the page boundary is crossed.
+    #[inline]
+    fn shift_access(&self) -> OperandAccess {
+        match self.variant {
+            CpuVariant::Wdc65C02 => OperandAccess::Read,
+            CpuVariant::Nmos6502
+            | CpuVariant::Ricoh2A03
+            | CpuVariant::Mos6510
+            | CpuVariant::Mos6507 => OperandAccess::ReadModifyWrite,
+        }
+    }
+
+    /// TRB and TSB: set Z as BIT does, and reset or set the bits set in A.
+    #[inline]
+    fn test_and_modify_bits(&mut self, addr_mode: AddressMode, set: bool) -> Result<(), RunError> {
+        let ea = self.get_effective_address(addr_mode, OperandAccess::ReadModifyWrite)?;
+        let value = self.read_data(ea)?;
+        self.modify_dummy_access(ea, value)?;
+        let a = self.reg_file.a();
+        self.reg_file
+            .set_flag_from_cond(Status::Zero, value & a == 0);
+        self.write_data(ea, if set { value | a } else { value & !a })?;
+
+        Ok(())
+    }
+
+    /// RMB and SMB: reset or set the bit, no flags are affected.
     #[inline]
-    fn read_modify_write_mem<F>(
-        &mut self,
-        addr_mode: AddressMode,
-        mut modify: F,
-    ) -> Result<(), RunError>
-    where
-        F: FnMut(u8) -> u8,
-    {
-        let ea = self.get_effective_address(addr_mode)?;
-        let value = self.read_u8(ea)?;
-        let value = modify(value);
-        self.write_u8(ea, value)?;
-        self.update_flags_nz(value);
+    fn modify_bit(&mut self, bit: u8, addr_mode: AddressMode, set: bool) -> Result<(), RunError> {
+        let ea = self.get_effective_address(addr_mode, OperandAccess::ReadModifyWrite)?;
+        let value = self.read_data(ea)?;
+        self.modify_dummy_access(ea, value)?;
+        let mask = 1 << bit;
+        self.write_data(ea, if set { value | mask } else { value & !mask })?;
 
         Ok(())
     }
@@ -372,7 +1879,7 @@ where
     #[inline]
     fn stack_push_u8(&mut self, value: u8) -> Result<(), RunError> {
         let sp = self.reg_file.sp();
-        self.write_u8(STACK_BOTTOM + sp as u16, value)?;
+        self.write_access(STACK_BOTTOM + sp as u16, value, AccessKind::Stack)?;
         *self.reg_file.sp_mut() = sp.wrapping_sub(1);
 
         if sp == u8::MAX && self.allow_stack_wraparound == StackWraparound::Disallow {
@@ -389,12 +1896,29 @@ where
             return Err(RunError::StackUnderflow);
         }
 
-        let value = self.read_u8(STACK_BOTTOM + sp as u16)?;
+        let value = self.read_access(STACK_BOTTOM + sp as u16, AccessKind::Stack)?;
         *self.reg_file.sp_mut() = sp;
 
         Ok(value)
     }
 
+    /// Reads from the stack top while the processor is busy incrementing
+    /// the stack pointer, only when stepping the bus cycle by cycle.
+    #[inline]
+    fn stack_dummy_read(&mut self) -> Result<(), RunError> {
+        self.dummy_read(STACK_BOTTOM + self.reg_file.sp() as u16)
+    }
+
+    #[inline]
+    fn stack_pull_to_reg(&mut self, reg: Register) -> Result<(), RunError> {
+        self.stack_dummy_read()?;
+        let value = self.stack_pull_u8()?;
+        *self.reg_file.reg_mut(reg) = value;
+        self.update_flags_nz(value);
+
+        Ok(())
+    }
+
     #[inline]
     fn stack_push_u16(&mut self, value: u16) -> Result<(), RunError> {
         self.stack_push_u8((value >> 8) as u8)?;
@@ -419,10 +1943,27 @@ where
     #[inline]
     fn branch(&mut self, addr_mode: AddressMode, cond: bool) -> Result<(), RunError> {
         if cond {
-            // Branch taken: get the offset
-            let ea = self.get_effective_address(addr_mode)?;
-            let offset = self.read_u8(ea)? as i8;
+            // Branch taken: get the offset. That takes an extra cycle,
+            // and one more if the branch goes to another page. On these
+            // cycles the processor reads the opcode following the branch,
+            // and then from the target address with the high byte not
+            // fixed yet.
+            let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+            let offset = self.fetch_u8(ea)? as i8;
+            let pc = self.reg_file.pc();
+            self.dummy_read(pc)?;
             self.reg_file.adjust_pc_by(offset);
+            self.cycles += 1;
+
+            let new_pc = self.reg_file.pc();
+            if (pc ^ new_pc) & 0xff00 != 0 {
+                self.dummy_read((pc & 0xff00) | (new_pc & 0x00ff))?;
+                self.cycles += 1;
+            }
+        } else if self.bus_mode == BusMode::CycleStepped {
+            // Branch not taken: the offset byte is still fetched
+            let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+            self.fetch_u8(ea)?;
         } else {
             // Branch not taken: skip the offset byte
             self.reg_file.adjust_pc_by(1);
@@ -430,44 +1971,73 @@ where
         Ok(())
     }
 
+    /// BBR and BBS: test the bit of the zero page location, and branch
+    /// if it is reset or set. The location is read twice.
+    #[inline]
+    fn branch_on_bit(
+        &mut self,
+        bit: u8,
+        addr_mode: AddressMode,
+        set: bool,
+    ) -> Result<(), RunError> {
+        let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+        let value = self.read_data(ea)?;
+        self.dummy_read(ea)?;
+        self.branch(AddressMode::Relative, (value >> bit) & 1 == set as u8)
+    }
+
     #[inline]
     fn compare_reg_mem(&mut self, reg: Register, addr_mode: AddressMode) -> Result<(), RunError> {
-        let ea = self.get_effective_address(addr_mode)?;
-        let memv = self.read_u8(ea)?;
+        let memv = self.read_operand(addr_mode)?;
+        self.compare_reg(reg, memv);
+
+        Ok(())
+    }
+
+    #[inline]
+    fn compare_reg(&mut self, reg: Register, memv: u8) {
         let regv = self.reg_file.reg(reg);
 
         self.update_flags_nz(regv.wrapping_sub(memv));
         self.reg_file
             .set_flag_from_cond(Status::Carry, regv >= memv);
-
-        Ok(())
     }
 
     #[inline]
     fn bit(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
-        let ea = self.get_effective_address(addr_mode)?;
-        let data = self.read_u8(ea)?;
+        let data = self.read_operand(addr_mode)?;
         let a = self.reg_file.a();
 
         self.reg_file
             .set_flag_from_cond(Status::Zero, data & a == 0);
-        self.reg_file
-            .set_flag_from_cond(Status::Negative, data & Status::Negative.mask() != 0);
-        self.reg_file
-            .set_flag_from_cond(Status::Overflow, data & Status::Overflow.mask() != 0);
+        // BIT immediate of the 65C02 affects only Z
+        if addr_mode != AddressMode::Immediate {
+            self.reg_file
+                .set_flag_from_cond(Status::Negative, data & Status::Negative.mask() != 0);
+            self.reg_file
+                .set_flag_from_cond(Status::Overflow, data & Status::Overflow.mask() != 0);
+        }
 
         Ok(())
     }
 
+    /// Whether ADC, SBC and ARR do decimal arithmetic. The Ricoh 2A03
+    /// has the decimal mode disabled.
+    #[inline]
+    fn decimal_mode(&self) -> bool {
+        self.variant != CpuVariant::Ricoh2A03 && self.flag_set(Status::Decimal)
+    }
+
     #[inline]
-    fn lsr(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+    fn lsr(&mut self, addr_mode: AddressMode) -> Result<u8, RunError> {
         let mut carry = false;
-        self.read_modify_write_mem(addr_mode, |v| {
+        let value = self.read_modify_write_mem(addr_mode, self.shift_access(), |v| {
             carry = v & 1 != 0;
             v.wrapping_shr(1)
         })?;
         self.reg_file.set_flag_from_cond(Status::Carry, carry);
-        Ok(())
+
+        Ok(value)
     }
 
     #[inline]
@@ -481,11 +2051,11 @@ where
     }
 
     #[inline]
-    fn rol(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+    fn rol(&mut self, addr_mode: AddressMode) -> Result<u8, RunError> {
         let mut carry = false;
         let carry_set = self.flag_set(Status::Carry);
 
-        self.read_modify_write_mem(addr_mode, |v| {
+        let value = self.read_modify_write_mem(addr_mode, self.shift_access(), |v| {
             carry = v >> 7 != 0;
             let v = v.rotate_left(1);
             if carry_set {
@@ -496,7 +2066,7 @@ where
         })?;
         self.reg_file.set_flag_from_cond(Status::Carry, carry);
 
-        Ok(())
+        Ok(value)
     }
 
     #[inline]
@@ -517,11 +2087,11 @@ where
     }
 
     #[inline]
-    fn ror(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+    fn ror(&mut self, addr_mode: AddressMode) -> Result<u8, RunError> {
         let mut carry = false;
         let carry_set = self.flag_set(Status::Carry);
 
-        self.read_modify_write_mem(addr_mode, |v| {
+        let value = self.read_modify_write_mem(addr_mode, self.shift_access(), |v| {
             carry = v & 1 != 0;
             let v = v.rotate_right(1);
             if carry_set {
@@ -532,7 +2102,7 @@ where
         })?;
         self.reg_file.set_flag_from_cond(Status::Carry, carry);
 
-        Ok(())
+        Ok(value)
     }
 
     #[inline]
@@ -553,15 +2123,15 @@ where
     }
 
     #[inline]
-    fn asl(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+    fn asl(&mut self, addr_mode: AddressMode) -> Result<u8, RunError> {
         let mut carry = false;
-        self.read_modify_write_mem(addr_mode, |v| {
+        let value = self.read_modify_write_mem(addr_mode, self.shift_access(), |v| {
             carry = v >> 7 != 0;
             v.wrapping_shl(1)
         })?;
         self.reg_file.set_flag_from_cond(Status::Carry, carry);
 
-        Ok(())
+        Ok(value)
     }
 
     #[inline]
@@ -574,122 +2144,240 @@ where
         self.reg_file.set_flag_from_cond(Status::Carry, carry);
     }
 
+    /// AND with the operand and rotate right. In the decimal mode, the
+    /// result is adjusted in the way similar to ADC.
     #[inline]
-    fn adc(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
-        let decimal = self.flag_set(Status::Decimal);
+    fn arr(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+        let value = self.read_operand(addr_mode)?;
         let carry_in = self.flag_set(Status::Carry) as u8;
-        let mut carry_out = false;
-        let mut overflow = self.flag_set(Status::Overflow);
-        let mut negative = self.flag_set(Status::Negative);
-        let mut zero = self.flag_set(Status::Zero);
+        let t = self.reg_file.a() & value;
+        let mut r = (t >> 1) | (carry_in << 7);
+
+        if !self.decimal_mode() {
+            self.update_flags_nz(r);
+            self.reg_file
+                .set_flag_from_cond(Status::Carry, r & 0b0100_0000 != 0);
+            self.reg_file
+                .set_flag_from_cond(Status::Overflow, ((r >> 6) ^ (r >> 5)) & 1 != 0);
+        } else {
+            self.reg_file
+                .set_flag_from_cond(Status::Negative, carry_in != 0);
+            self.reg_file.set_flag_from_cond(Status::Zero, r == 0);
+            self.reg_file
+                .set_flag_from_cond(Status::Overflow, (r ^ t) & 0b0100_0000 != 0);
+
+            let (hi, lo) = (t >> 4, t & 0x0f);
+            if lo + (lo & 1) > 5 {
+                r = (r & 0xf0) | (r.wrapping_add(6) & 0x0f);
+            }
+            let carry = hi + (hi & 1) > 5;
+            if carry {
+                r = r.wrapping_add(0x60);
+            }
+            self.reg_file.set_flag_from_cond(Status::Carry, carry);
+        }
+        *self.reg_file.a_mut() = r;
 
-        let a = self.reg_file.a();
-        self.read_modify_to_reg(addr_mode, Register::A, |v| {
-            let r = if !decimal {
-                let v = v as u16;
-                let a = a as u16;
-                let r = v.wrapping_add(a).wrapping_add(carry_in as u16);
-
-                // Signed overflow happens if the sign of the operands is different
-                // from sign of the result, and either the sum of two positive numbers
-                // can't be represented as a positive number or the sum of the negative
-                // numbers can't be represented as a negative number. Refer to
-                // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
-                // for the in depth explanation.
-                overflow = (a ^ r) & (v ^ r) & 0x0080 != 0;
-                carry_out = r & 0xff00 != 0;
-
-                r
-            } else {
-                let mut r = bcd_to_u8(a) + bcd_to_u8(v) + carry_in;
-                carry_out = r > 99;
-                if carry_out {
-                    r -= 100;
-                }
+        Ok(())
+    }
 
-                u8_to_bcd(r) as u16
-            };
+    /// The 65C02 takes an extra cycle for ADC and SBC in the decimal mode,
+    /// reading the operand once more.
+    #[inline]
+    fn decimal_extra_cycle(&mut self, ea: u16) -> Result<(), RunError> {
+        if self.variant == CpuVariant::Wdc65C02 && self.decimal_mode() {
+            self.dummy_read(ea)?;
+            self.cycles += 1;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn adc(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
+        let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+        let value = self.read_operand_at(ea, addr_mode)?;
+        self.decimal_extra_cycle(ea)?;
+        self.add_with_carry(value);
 
-            zero = r & 0xff == 0;
-            negative = r & 0x80 != 0;
+        Ok(())
+    }
 
-            r as u8
-        })?;
+    #[inline]
+    fn set_decimal_outcome(&mut self, outcome: BcdOutcome) {
+        *self.reg_file.a_mut() = outcome.value;
+        self.reg_file
+            .set_flag_from_cond(Status::Carry, outcome.carry);
+        self.reg_file
+            .set_flag_from_cond(Status::Overflow, outcome.overflow);
+        self.reg_file
+            .set_flag_from_cond(Status::Negative, outcome.negative);
+        self.reg_file.set_flag_from_cond(Status::Zero, outcome.zero);
+    }
+
+    #[inline]
+    fn add_with_carry(&mut self, v: u8) {
+        let carry = self.flag_set(Status::Carry);
+        let a = self.reg_file.a();
 
+        if self.decimal_mode() {
+            let outcome = match self.variant {
+                CpuVariant::Wdc65C02 => cmos_add(a, v, carry),
+                _ => nmos_add(a, v, carry),
+            };
+            self.set_decimal_outcome(outcome);
+            return;
+        }
+
+        let v = v as u16;
+        let a = a as u16;
+        let r = v.wrapping_add(a).wrapping_add(carry as u16);
+
+        // Signed overflow happens if the sign of the operands is different
+        // from sign of the result, and either the sum of two positive numbers
+        // can't be represented as a positive number or the sum of the negative
+        // numbers can't be represented as a negative number. Refer to
+        // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
+        // for the in depth explanation.
+        let overflow = (a ^ r) & (v ^ r) & 0x0080 != 0;
+        let carry_out = r & 0xff00 != 0;
+        let r = r as u8;
+
+        *self.reg_file.a_mut() = r;
         self.reg_file.set_flag_from_cond(Status::Carry, carry_out);
         self.reg_file.set_flag_from_cond(Status::Overflow, overflow);
-        self.reg_file.set_flag_from_cond(Status::Negative, negative);
-        self.reg_file.set_flag_from_cond(Status::Zero, zero);
-
-        Ok(())
+        self.update_flags_nz(r);
     }
 
     #[inline]
     fn sbc(&mut self, addr_mode: AddressMode) -> Result<(), RunError> {
-        let decimal = self.flag_set(Status::Decimal);
+        let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
+        let value = self.read_operand_at(ea, addr_mode)?;
+        self.decimal_extra_cycle(ea)?;
+        self.subtract_with_borrow(value);
 
-        // In the hardware, `sbc operand` is `adc ~operand` (or 255-operand, i.e.
-        // 1-compliment). There is no explicit borrow flag, instead the complement
-        // of the carry flag is used.
-        let borrow_in = !self.flag_set(Status::Carry) as u8;
-        let mut borrow_out = false;
-        let mut overflow = self.flag_set(Status::Overflow);
-        let mut negative = self.flag_set(Status::Negative);
-        let mut zero = self.flag_set(Status::Zero);
+        Ok(())
+    }
 
+    #[inline]
+    fn subtract_with_borrow(&mut self, v: u8) {
         let a = self.reg_file.a();
-        self.read_modify_to_reg(addr_mode, Register::A, |v| {
-            let r = if !decimal {
-                let a = a as u16;
-                let v = v as u16;
-                let r = a.wrapping_sub(v).wrapping_sub(borrow_in as u16);
-                borrow_out = r & 0xff00 != 0;
-
-                // Signed overflow happens if the sign of the operands is different
-                // from sign of the result, and either the sum of two positive numbers
-                // can't be represented as a positive number or the sum of the negative
-                // numbers can't be represented as a negative number. Refer to
-                // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
-                // for the in depth explanation.
-                overflow = (a ^ r) & (!v ^ r) & 0x0080 != 0;
-
-                r
-            } else {
-                let mut r = bcd_to_u8(a)
-                    .wrapping_sub(bcd_to_u8(v))
-                    .wrapping_sub(borrow_in) as i8;
-                borrow_out = r < 0;
-                if borrow_out {
-                    r += 100;
-                }
 
-                u8_to_bcd(r as u8) as u16
+        if self.decimal_mode() {
+            let carry = self.flag_set(Status::Carry);
+            let outcome = match self.variant {
+                CpuVariant::Wdc65C02 => cmos_sub(a, v, carry),
+                _ => nmos_sub(a, v, carry),
             };
+            self.set_decimal_outcome(outcome);
+            return;
+        }
 
-            zero = r & 0xff == 0;
-            negative = r & 0x80 != 0;
-
-            r as u8
-        })?;
+        // In the hardware, `sbc operand` is `adc ~operand` (or 255-operand, i.e.
+        // 1-compliment). There is no explicit borrow flag, instead the complement
+        // of the carry flag is used.
+        let borrow_in = !self.flag_set(Status::Carry) as u8;
 
+        let a = a as u16;
+        let v = v as u16;
+        let r = a.wrapping_sub(v).wrapping_sub(borrow_in as u16);
+
+        // Signed overflow happens if the sign of the operands is different
+        // from sign of the result, and either the sum of two positive numbers
+        // can't be represented as a positive number or the sum of the negative
+        // numbers can't be represented as a negative number. Refer to
+        // https://www.righto.com/2012/12/the-6502-overflow-flag-explained.html
+        // for the in depth explanation.
+        let overflow = (a ^ r) & (!v ^ r) & 0x0080 != 0;
+        let borrow_out = r & 0xff00 != 0;
+        let r = r as u8;
+
+        *self.reg_file.a_mut() = r;
         self.reg_file.set_flag_from_cond(Status::Carry, !borrow_out);
         self.reg_file.set_flag_from_cond(Status::Overflow, overflow);
-        self.reg_file.set_flag_from_cond(Status::Negative, negative);
-        self.reg_file.set_flag_from_cond(Status::Zero, zero);
+        self.update_flags_nz(r);
+    }
 
-        Ok(())
+    /// Disables interrupts when entering the handler. The 65C02 also clears
+    /// the decimal flag.
+    #[inline]
+    fn enter_interrupt_handler(&mut self) {
+        self.reg_file.set_flag(Status::InterruptDisable);
+        if self.variant == CpuVariant::Wdc65C02 {
+            self.reg_file.clear_flag(Status::Decimal);
+        }
+    }
+
+    #[inline]
+    fn is_trap(&self, opcode: u8) -> bool {
+        (self.brk_trap && opcode == 0x00) || self.opcode_trap == Some(opcode)
+    }
+
+    /// The processor as the host functions see it
+    fn host_call<R>(
+        &mut self,
+        call: impl FnOnce(&mut H, &mut HostBus<'_, M>) -> Result<R, RunError>,
+    ) -> Result<R, RunError> {
+        let mut bus = HostBus {
+            registers: &mut self.reg_file,
+            memory: &mut self.mem,
+            io_port: &mut self.io_port,
+            variant: self.variant,
+            writes: BusWrites {
+                buffer: &mut self.write_buffer,
+                undo_log: &mut self.undo_log,
+            },
+        };
+        call(&mut self.host, &mut bus)
     }
 
     fn step(&mut self) -> Result<RunExit, RunError> {
         // Fetch instruction
         self.last_opcode = self
-            .mem
-            .read(self.reg_file.pc())
+            .bus_read(self.reg_file.pc(), AccessKind::OpcodeFetch)
             .map_err(RunError::CannotFetchInstruction)?;
         self.reg_file.adjust_pc_by(1);
 
-        // Decode instruction from the opcode
-        let insn = crate::insns::decode_insn(self.last_opcode);
+        if self.is_trap(self.last_opcode) {
+            let opcode = self.last_opcode;
+            let signature = self.fetch_u8(self.reg_file.pc())?;
+            self.reg_file.adjust_pc_by(1);
+            self.cycles += TRAP_CYCLES as u64;
+            self.host_call(|host, bus| host.trap(opcode, signature, bus))?;
+
+            return Ok(RunExit::Trapped(signature));
+        }
+
+        // Dispatch to the handler specialized for the opcode
+        let opcode = self.last_opcode as usize;
+        let handler = match (self.variant, self.undocumented_opcodes) {
+            (CpuVariant::Wdc65C02, _) => Self::WDC_65C02_HANDLERS[opcode],
+            (_, UndocumentedOpcodes::Allow) => Self::NMOS_UNDOCUMENTED_HANDLERS[opcode],
+            (_, UndocumentedOpcodes::Disallow) => Self::NMOS_HANDLERS[opcode],
+        };
+
+        handler(self)
+    }
+
+    /// Executes the instruction of the opcode. The instruction and its cycles
+    /// are decoded at compile time, and the shared `execute` takes them
+    /// from there.
+    fn execute_opcode<const DECODER: u8, const OPCODE: u8>(&mut self) -> Result<RunExit, RunError> {
+        let insn = const { decode(DECODER, OPCODE) };
+        let base_cycles = const { opcode_cycles(DECODER, OPCODE) };
+
+        self.execute(insn, base_cycles)
+    }
+
+    fn execute(&mut self, insn: Insn, base_cycles: u8) -> Result<RunExit, RunError> {
+        self.cycles += base_cycles as u64;
+
+        // The instructions without operands read the byte following
+        // the opcode and ignore it (BRK skips it as the break mark).
+        // The single-cycle NOPs of the 65C02 don't.
+        if insn.is_valid() && insn.addr_mode().is_none() && base_cycles > 1 {
+            self.dummy_read(self.reg_file.pc())?;
+        }
 
         // Execute instruction
         match insn {
@@ -704,9 +2392,16 @@ where
                 let p = self.reg_file.reg(Register::P);
                 self.stack_push_u8(p | Status::Break.mask() | Status::AlwaysSet.mask())?;
                 // Disable interrupts
-                self.reg_file.set_flag(Status::InterruptDisable);
-                let new_pc = self.read_u16(IRQ_BRK_VECTOR)?;
+                self.enter_interrupt_handler();
+                // An NMI arriving meanwhile takes over the vector
+                let hijacked = self.interrupt_timing == InterruptTiming::Hardware
+                    && self.nmi_pending.load(Ordering::Acquire);
+                let vector = if hijacked { NMI_VECTOR } else { IRQ_BRK_VECTOR };
+                let new_pc = self.read_vector(vector)?;
                 self.reg_file.set_pc(new_pc);
+                if hijacked {
+                    self.nmi_pending.store(false, Ordering::Release);
+                }
             }
             Insn::BCC(addr_mode) => self.branch(addr_mode, !self.flag_set(Status::Carry))?,
             Insn::BCS(addr_mode) => self.branch(addr_mode, self.flag_set(Status::Carry))?,
@@ -717,6 +2412,7 @@ where
             Insn::BPL(addr_mode) => self.branch(addr_mode, !self.flag_set(Status::Negative))?,
             Insn::BMI(addr_mode) => self.branch(addr_mode, self.flag_set(Status::Negative))?,
             Insn::BIT(addr_mode) => self.bit(addr_mode)?,
+            Insn::BRA(addr_mode) => self.branch(addr_mode, true)?,
             Insn::CLC => self.reg_file.clear_flag(Status::Carry),
             Insn::CLD => self.reg_file.clear_flag(Status::Decimal),
             Insn::CLI => self.reg_file.clear_flag(Status::InterruptDisable),
@@ -727,12 +2423,23 @@ where
             Insn::INX => self.read_modify_write_reg(Register::X, |v| v.wrapping_add(1)),
             Insn::INY => self.read_modify_write_reg(Register::Y, |v| v.wrapping_add(1)),
             Insn::JMP(addr_mode) => {
-                let ea = self.get_effective_address(addr_mode)?;
+                let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
                 self.reg_file.set_pc(ea);
             }
+            Insn::JSR(_) if self.bus_mode == BusMode::CycleStepped => {
+                // The processor fetches the low byte of the subroutine address,
+                // pushes the address of the high byte (the return address for RTS
+                // less one), and only then fetches the high byte.
+                let lo = self.fetch_u8(self.reg_file.pc())?;
+                self.reg_file.adjust_pc_by(1);
+                self.stack_dummy_read()?;
+                self.stack_push_u16(self.reg_file.pc())?;
+                let hi = self.fetch_u8(self.reg_file.pc())?;
+                self.reg_file.set_pc(u16::from_le_bytes([lo, hi]));
+            }
             Insn::JSR(addr_mode) => {
                 // Get the new PC location (which also skips the JSR instruction bytes)
-                let ea = self.get_effective_address(addr_mode)?;
+                let ea = self.get_effective_address(addr_mode, OperandAccess::Read)?;
                 // Now the PC is right after the JSR operation along with its operand.
                 // This PC will be the return address for RTS, push it to the stack
                 self.stack_push_u16(self.reg_file.pc().wrapping_sub(1))?;
@@ -745,18 +2452,16 @@ where
                 let p = self.reg_file.reg(Register::P);
                 self.stack_push_u8(p | Status::Break.mask() | Status::AlwaysSet.mask())?;
             }
-            Insn::PLA => {
-                let value = self.stack_pull_u8()?;
-                *self.reg_file.a_mut() = value;
-                self.update_flags_nz(value);
-            }
+            Insn::PLA => self.stack_pull_to_reg(Register::A)?,
             Insn::PLP => {
+                self.stack_dummy_read()?;
                 let value = self.stack_pull_u8()?;
                 *self.reg_file.reg_mut(Register::P) = value;
                 self.reg_file.clear_flag(Status::Break);
                 self.reg_file.set_flag(Status::AlwaysSet);
             }
             Insn::RTI => {
+                self.stack_dummy_read()?;
                 let value = self.stack_pull_u8()?;
                 *self.reg_file.reg_mut(Register::P) = value;
                 self.reg_file.clear_flag(Status::Break);
@@ -766,7 +2471,10 @@ where
                 self.reg_file.set_pc(pc);
             }
             Insn::RTS => {
+                self.stack_dummy_read()?;
                 let pc = self.stack_pull_u16()?;
+                // The processor reads at the pulled address while incrementing it
+                self.dummy_read(pc)?;
                 self.reg_file.set_pc(pc.wrapping_add(1));
             }
             Insn::SEC => self.reg_file.set_flag(Status::Carry),
@@ -800,18 +2508,26 @@ where
             // Group 0b10. Bit operation and accumulator operations,
             // less regular than the ALU group.
             Insn::ASLA => self.asla(),
-            Insn::ASL(addr_mode) => self.asl(addr_mode)?,
-            Insn::DEC(addr_mode) => self.read_modify_write_mem(addr_mode, |v| v.wrapping_sub(1))?,
+            Insn::ASL(addr_mode) => _ = self.asl(addr_mode)?,
+            Insn::DEC(addr_mode) => {
+                _ = self.read_modify_write_mem(addr_mode, OperandAccess::ReadModifyWrite, |v| {
+                    v.wrapping_sub(1)
+                })?
+            }
             Insn::DEX => self.read_modify_write_reg(Register::X, |v| v.wrapping_sub(1)),
-            Insn::INC(addr_mode) => self.read_modify_write_mem(addr_mode, |v| v.wrapping_add(1))?,
+            Insn::INC(addr_mode) => {
+                _ = self.read_modify_write_mem(addr_mode, OperandAccess::ReadModifyWrite, |v| {
+                    v.wrapping_add(1)
+                })?
+            }
             Insn::LDX(addr_mode) => self.mem_to_reg(addr_mode, Register::X)?,
             Insn::LSRA => self.lsra(),
-            Insn::LSR(addr_mode) => self.lsr(addr_mode)?,
+            Insn::LSR(addr_mode) => _ = self.lsr(addr_mode)?,
             Insn::NOP => {}
             Insn::ROLA => self.rola(),
-            Insn::ROL(addr_mode) => self.rol(addr_mode)?,
+            Insn::ROL(addr_mode) => _ = self.rol(addr_mode)?,
             Insn::RORA => self.rora(),
-            Insn::ROR(addr_mode) => self.ror(addr_mode)?,
+            Insn::ROR(addr_mode) => _ = self.ror(addr_mode)?,
             Insn::STX(addr_mode) => self.reg_to_mem(Register::X, addr_mode)?,
             Insn::TAX => self.reg_to_reg(Register::A, Register::X),
             Insn::TSX => self.reg_to_reg(Register::S, Register::X),
@@ -819,24 +2535,328 @@ where
             // Note: TXS does not update flags!
             Insn::TXS => *self.reg_file.sp_mut() = self.reg_file.x(),
 
-            // Group 0b11 contains invalid instructions
-            Insn::JAM => {
-                return Err(RunError::InvalidInstruction(self.last_opcode));
+            // Undocumented instructions, mostly from the group 0b11. They
+            // combine the operations of the groups 0b01 and 0b10.
+            Insn::ALR(addr_mode) => {
+                let a = self.reg_file.a();
+                self.read_modify_to_reg(addr_mode, Register::A, |v| v & a)?;
+                self.lsra();
+            }
+            Insn::ANC(addr_mode) => {
+                let a = self.reg_file.a();
+                self.read_modify_to_reg(addr_mode, Register::A, |v| v & a)?;
+                let negative = self.flag_set(Status::Negative);
+                self.reg_file.set_flag_from_cond(Status::Carry, negative);
+            }
+            Insn::ARR(addr_mode) => self.arr(addr_mode)?,
+            Insn::DCP(addr_mode) => {
+                let value =
+                    self.read_modify_write_mem(addr_mode, OperandAccess::ReadModifyWrite, |v| {
+                        v.wrapping_sub(1)
+                    })?;
+                self.compare_reg(Register::A, value);
+            }
+            Insn::ISC(addr_mode) => {
+                let value =
+                    self.read_modify_write_mem(addr_mode, OperandAccess::ReadModifyWrite, |v| {
+                        v.wrapping_add(1)
+                    })?;
+                self.subtract_with_borrow(value);
+            }
+            Insn::LAX(addr_mode) => {
+                self.mem_to_reg(addr_mode, Register::A)?;
+                *self.reg_file.x_mut() = self.reg_file.a();
+            }
+            Insn::NOPM(addr_mode) => _ = self.read_operand(addr_mode)?,
+            Insn::RLA(addr_mode) => {
+                let value = self.rol(addr_mode)?;
+                self.read_modify_write_reg(Register::A, |a| a & value);
+            }
+            Insn::RRA(addr_mode) => {
+                let value = self.ror(addr_mode)?;
+                self.add_with_carry(value);
+            }
+            Insn::SAX(addr_mode) => {
+                let ea = self.get_effective_address(addr_mode, OperandAccess::Write)?;
+                self.write_data(ea, self.reg_file.a() & self.reg_file.x())?;
+            }
+            Insn::SBX(addr_mode) => {
+                let value = self.read_operand(addr_mode)?;
+                let ax = self.reg_file.a() & self.reg_file.x();
+                *self.reg_file.x_mut() = ax.wrapping_sub(value);
+                self.update_flags_nz(ax.wrapping_sub(value));
+                self.reg_file.set_flag_from_cond(Status::Carry, ax >= value);
+            }
+            Insn::SLO(addr_mode) => {
+                let value = self.asl(addr_mode)?;
+                self.read_modify_write_reg(Register::A, |a| a | value);
+            }
+            Insn::SRE(addr_mode) => {
+                let value = self.lsr(addr_mode)?;
+                self.read_modify_write_reg(Register::A, |a| a ^ value);
+            }
+
+            // The 65C02 instructions
+            Insn::DECA => self.read_modify_write_reg(Register::A, |v| v.wrapping_sub(1)),
+            Insn::INCA => self.read_modify_write_reg(Register::A, |v| v.wrapping_add(1)),
+            Insn::PHX => self.stack_push_u8(self.reg_file.x())?,
+            Insn::PHY => self.stack_push_u8(self.reg_file.y())?,
+            Insn::PLX => self.stack_pull_to_reg(Register::X)?,
+            Insn::PLY => self.stack_pull_to_reg(Register::Y)?,
+            Insn::STZ(addr_mode) => {
+                let ea = self.get_effective_address(addr_mode, OperandAccess::Write)?;
+                self.write_data(ea, 0)?;
+            }
+            Insn::TRB(addr_mode) => self.test_and_modify_bits(addr_mode, false)?,
+            Insn::TSB(addr_mode) => self.test_and_modify_bits(addr_mode, true)?,
+            Insn::BBR(bit, addr_mode) => self.branch_on_bit(bit, addr_mode, false)?,
+            Insn::BBS(bit, addr_mode) => self.branch_on_bit(bit, addr_mode, true)?,
+            Insn::RMB(bit, addr_mode) => self.modify_bit(bit, addr_mode, false)?,
+            Insn::SMB(bit, addr_mode) => self.modify_bit(bit, addr_mode, true)?,
+            Insn::STP => {
+                self.dummy_read(self.reg_file.pc())?;
+                self.exec_state = ExecState::Stopped;
             }
+            Insn::WAI => {
+                self.dummy_read(self.reg_file.pc())?;
+                self.exec_state = ExecState::Waiting;
+            }
+
+            // The rest of the group 0b11 and a few other opcodes jam
+            Insn::JAM => match self.jam_policy {
+                JamPolicy::Fault => return Err(RunError::InvalidInstruction(self.last_opcode)),
+                JamPolicy::Freeze => self.exec_state = ExecState::Stopped,
+                JamPolicy::Nop => {
+                    let operand_bytes = get_operand_bytes(self.last_opcode);
+                    self.reg_file.adjust_pc_by(operand_bytes as i8);
+                    // The opcodes halting the processor take no cycles otherwise
+                    if base_cycles == 0 {
+                        self.cycles += 2;
+                    }
+                }
+                JamPolicy::Host => {
+                    let opcode = self.last_opcode;
+                    let cycles = self.host_call(|host, bus| host.jam(opcode, bus))?;
+                    self.cycles = self.cycles - base_cycles as u64 + cycles as u64;
+                }
+            },
         };
 
         Ok(RunExit::Executed(insn))
     }
 
+    /// The processor fetches the opcode and the byte following it before
+    /// entering the interrupt handler, and discards both.
+    #[inline]
+    fn interrupt_dummy_reads(&mut self) -> Result<(), RunError> {
+        let pc = self.reg_file.pc();
+        self.dummy_read(pc)?;
+        self.dummy_read(pc)
+    }
+
+    /// NMI takes precedence over IRQ.
+    fn poll_interrupts(&self, irq_disabled: bool) -> Option<Interrupt> {
+        if self.nmi_pending.load(Ordering::Acquire) {
+            Some(Interrupt::Nmi)
+        } else if !irq_disabled && self.irq_sources() != 0 {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        }
+    }
+
+    fn enter_interrupt(&mut self, interrupt: Interrupt) -> Result<RunExit, RunError> {
+        self.exec_state = ExecState::Running;
+        self.interrupt_dummy_reads()?;
+        self.stack_push_u16(self.reg_file.pc())?;
+
+        let p = self.reg_file.reg(Register::P);
+        self.stack_push_u8(p & !Status::Break.mask() | Status::AlwaysSet.mask())?;
+        self.enter_interrupt_handler();
+
+        // An NMI arriving during the IRQ entry takes over the vector
+        let interrupt = if self.interrupt_timing == InterruptTiming::Hardware
+            && self.nmi_pending.load(Ordering::Acquire)
+        {
+            Interrupt::Nmi
+        } else {
+            interrupt
+        };
+        match interrupt {
+            Interrupt::Nmi => {
+                let new_pc = self.read_vector(NMI_VECTOR)?;
+                self.reg_file.set_pc(new_pc);
+                self.nmi_pending.store(false, Ordering::Release);
+                self.cycles += INTERRUPT_CYCLES as u64;
+
+                Ok(RunExit::NonMaskableInterrupt)
+            }
+            Interrupt::Irq => {
+                let new_pc = self.read_vector(IRQ_BRK_VECTOR)?;
+                self.reg_file.set_pc(new_pc);
+                self.cycles += INTERRUPT_CYCLES as u64;
+
+                Ok(RunExit::Interrupt)
+            }
+        }
+    }
+
+    /// Runs until the budget runs out, an instruction retires with the
+    /// program counter in the stop set, or anything but an instruction
+    /// retiring happens, e.g. an interrupt entry or a fault. The cycles
+    /// a device stalls the processor for are charged against the budget.
+    /// Nothing but the processor changes the lines during the batch, so
+    /// waiting for an interrupt or with RDY held low idles until the cycle
+    /// budget runs out, and ends the batch with an instruction budget.
+    pub fn run_batch(&mut self, budget: Budget, stop_pcs: &[u16]) -> BatchSummary {
+        let cycles = self.cycles;
+        let mut instructions = 0;
+        // The lines are loaded again only after the processor has acted on
+        // them, and while none needs attention, the instructions run without
+        // checking them.
+        let mut quiet = self.quiet_irq();
+
+        let exit = loop {
+            let exhausted = match budget {
+                Budget::Instructions(budget) => instructions >= budget,
+                Budget::Cycles(budget) => self.cycles - cycles >= budget,
+            };
+            if exhausted {
+                break BatchExit::BudgetExhausted;
+            }
+
+            let run_cycles = self.cycles;
+            let run = match quiet {
+                Some(irq) => self.run_quiet(irq),
+                None => self.run_once(),
+            };
+            self.last_run_cycles = self.cycles - run_cycles;
+            match run {
+                Ok(RunExit::Executed(_) | RunExit::Trapped(_)) => instructions += 1,
+                Ok(RunExit::Stalled(_)) if !self.rdy_low.load(Ordering::Acquire) => continue,
+                Ok(exit @ (RunExit::Waiting | RunExit::Stalled(_))) => match budget {
+                    Budget::Cycles(budget) => {
+                        self.cycles = self.cycles.max(cycles + budget);
+                        break BatchExit::BudgetExhausted;
+                    }
+                    Budget::Instructions(_) => break BatchExit::Event(exit),
+                },
+                Ok(exit) => break BatchExit::Event(exit),
+                Err(e) => break BatchExit::Fault(e),
+            }
+
+            quiet = match quiet {
+                Some(irq)
+                    if self.exec_state == ExecState::Running
+                        && self.polled_interrupt.is_none()
+                        && (irq == 0 || self.flag_set(Status::InterruptDisable)) =>
+                {
+                    Some(irq)
+                }
+                Some(_) => None,
+                None => self.quiet_irq(),
+            };
+
+            let pc = self.reg_file.pc();
+            if stop_pcs.contains(&pc) {
+                break BatchExit::StopPc(pc);
+            }
+        };
+
+        BatchSummary {
+            exit,
+            instructions,
+            cycles: self.cycles - cycles,
+        }
+    }
+
+    /// Runs as `run` does, recording the state to step back to into
+    /// the history. The old values of the written cells, the host
+    /// functions' included, are looked at with `Memory::peek` before
+    /// writing. Where it returns `None`, as by default, they are read
+    /// with `Memory::read_access` instead: the memory sees a data read
+    /// of every cell before it is written, and the side effects of
+    /// the read, if any, happen.
+    pub fn run_recorded<const N: usize>(
+        &mut self,
+        history: &mut UndoHistory<N>,
+    ) -> Result<RunExit, RunError> {
+        let snapshot = self.snapshot();
+        self.undo_log.clear();
+        self.undo_log.active = true;
+        let run = self.run();
+        self.undo_log.active = false;
+
+        history.push(UndoRecord {
+            snapshot,
+            writes: self.undo_log.writes,
+            len: self.undo_log.len,
+            exact: !self.undo_log.incomplete,
+        });
+
+        run
+    }
+
+    /// Steps back over the last recorded `run`, restoring the processor
+    /// state and the memory cells it has written. Returns `false` if
+    /// the history is empty, or the run has written the memory in a way
+    /// not recorded (`UndoHistory::can_step_back`).
+    pub fn step_back<const N: usize>(
+        &mut self,
+        history: &mut UndoHistory<N>,
+    ) -> Result<bool, RunError> {
+        if !history.can_step_back() {
+            return Ok(false);
+        }
+        let Some(record) = history.pop() else {
+            return Ok(false);
+        };
+
+        for &(addr, value) in record.writes[..record.len].iter().rev() {
+            self.mem.poke(addr, value).map_err(RunError::MemoryAccess)?;
+        }
+        // Taken by `snapshot`, always valid
+        let restored = self.restore(&record.snapshot);
+        debug_assert!(restored.is_ok());
+
+        Ok(true)
+    }
+
+    /// Steps back until the condition holds, e.g. the program counter
+    /// is at the address. Returns `false` if the history has run out first,
+    /// or can't be stepped back over as `step_back` tells.
+    pub fn run_back_until<const N: usize>(
+        &mut self,
+        history: &mut UndoHistory<N>,
+        mut until: impl FnMut(&Self) -> bool,
+    ) -> Result<bool, RunError> {
+        while self.step_back(history)? {
+            if until(self) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
     pub fn run(&mut self) -> Result<RunExit, RunError> {
+        let cycles = self.cycles;
+        let run = self.run_once();
+        self.last_run_cycles = self.cycles - cycles;
+
+        run
+    }
+
+    fn run_once(&mut self) -> Result<RunExit, RunError> {
         // Handle reset.
         // The real processor can't/won't deaasert the line.
         if self.reset_pending.load(Ordering::Acquire) {
-            let new_pc = self.read_u16(RESET_VECTOR)?;
-            self.reg_file.set_pc(new_pc);
-            self.fault = None;
-            self.reg_file.reset();
-            self.reset_pending.store(false, Ordering::Release);
+            // A failed reset leaves the processor as it was, to be retried.
+            let cycles = self.cycles;
+            if let Err(e) = self.reset_sequence() {
+                self.cycles = cycles;
+                return Err(e);
+            }
         }
 
         // If the processor faulted, refuse to run.
@@ -844,48 +2864,216 @@ where
             return Err(f);
         }
 
+        // RDY low halts the processor on the next read cycle, the opcode fetch.
+        let mut stolen =
+            core::mem::take(&mut self.stolen_cycles).saturating_add(self.mem.stall_cycles());
+        if stolen == 0 && self.rdy_low.load(Ordering::Acquire) {
+            stolen = 1;
+        }
+        if stolen != 0 {
+            self.cycles += stolen as u64;
+            return Ok(RunExit::Stalled(stolen));
+        }
+
+        // The SO pin sets V on the instruction boundary.
+        if self.so_pending.swap(false, Ordering::AcqRel) {
+            self.reg_file.set_flag(Status::Overflow);
+        }
+
+        // Stopped by STP, only reset will help.
+        if self.exec_state == ExecState::Stopped {
+            self.cycles += 1;
+            return Ok(RunExit::Stopped);
+        }
+
         // Handle other events.
         // The real processor can't/won't deaasert these lines.
-        if self.nmi_pending.load(Ordering::Acquire) {
-            self.reg_file.set_flag(Status::InterruptDisable);
-            self.stack_push_u16(self.reg_file.pc())?;
+        let mut interrupt = match self.interrupt_timing {
+            InterruptTiming::Immediate => {
+                self.poll_interrupts(self.flag_set(Status::InterruptDisable))
+            }
+            InterruptTiming::Hardware => self.polled_interrupt.take(),
+        };
+        if self.exec_state == ExecState::Waiting {
+            if self.nmi_pending.load(Ordering::Acquire) || self.irq_sources() != 0 {
+                // WAI resumes on IRQ even if interrupts are disabled, then the execution
+                // continues with the next instruction.
+                self.exec_state = ExecState::Running;
+            }
+            interrupt = self.poll_interrupts(self.flag_set(Status::InterruptDisable));
+        }
+        // Waiting for an interrupt after WAI.
+        if interrupt.is_none() && self.exec_state == ExecState::Waiting {
+            self.cycles += 1;
+            return Ok(RunExit::Waiting);
+        }
 
-            let p = self.reg_file.reg(Register::P);
-            self.stack_push_u8(p & !Status::Break.mask() | Status::AlwaysSet.mask())?;
+        self.advance(interrupt, true)
+    }
 
-            let new_pc = self.read_u16(NMI_VECTOR)?;
-            self.reg_file.set_pc(new_pc);
-            self.nmi_pending.store(false, Ordering::Release);
+    /// The reset sequence is an interrupt entry with the stack writes turned
+    /// into reads. Nothing is changed until the vector has been pulled.
+    fn reset_sequence(&mut self) -> Result<(), RunError> {
+        let pc = self.reg_file.pc();
+        self.dummy_read(pc)?;
+        self.dummy_read(pc)?;
+        let hardware = self.reset_mode == ResetMode::Hardware;
+        let sp = if hardware && self.power_on {
+            0
+        } else {
+            self.reg_file.sp()
+        };
+        for i in 0..3 {
+            self.dummy_read(STACK_BOTTOM + sp.wrapping_sub(i) as u16)?;
+        }
+        let new_pc = self.read_vector(RESET_VECTOR)?;
 
-            return Ok(RunExit::NonMaskableInterrupt);
+        if hardware {
+            *self.reg_file.sp_mut() = sp.wrapping_sub(3);
         }
-        if !self.flag_set(Status::InterruptDisable) && self.irq_pending.load(Ordering::Acquire) {
-            self.reg_file.set_flag(Status::InterruptDisable);
-            self.stack_push_u16(self.reg_file.pc())?;
+        self.power_on = false;
+        self.reg_file.set_pc(new_pc);
+        self.fault = None;
+        self.exec_state = ExecState::Running;
+        self.polled_interrupt = None;
+        self.io_port.reset();
+        self.reg_file.reset();
+        self.reset_pending.store(false, Ordering::Release);
+        self.cycles += RESET_CYCLES as u64;
 
-            let p = self.reg_file.reg(Register::P);
-            self.stack_push_u8(p & !Status::Break.mask() | Status::AlwaysSet.mask())?;
+        Ok(())
+    }
 
-            let new_pc = self.read_u16(IRQ_BRK_VECTOR)?;
-            self.reg_file.set_pc(new_pc);
-            self.irq_pending.store(false, Ordering::Release);
+    /// The IRQ sources if none of the lines needs attention before
+    /// the next instruction: no reset, no stall, no pending NMI or SO,
+    /// and no IRQ to take.
+    fn quiet_irq(&self) -> Option<u32> {
+        let irq = self.irq_sources();
+        let quiet = !self.reset_pending.load(Ordering::Acquire)
+            && !self.rdy_low.load(Ordering::Acquire)
+            && !self.so_pending.load(Ordering::Acquire)
+            && !self.nmi_pending.load(Ordering::Acquire)
+            && self.stolen_cycles == 0
+            && self.fault.is_none()
+            && self.exec_state == ExecState::Running
+            && self.polled_interrupt.is_none()
+            && (irq == 0 || self.flag_set(Status::InterruptDisable));
+
+        quiet.then_some(irq)
+    }
 
-            return Ok(RunExit::Interrupt);
+    /// Runs the next instruction as `run` does while the lines are quiet,
+    /// the IRQ sources being `irq`. Only the memory might stall it.
+    fn run_quiet(&mut self, irq: u32) -> Result<RunExit, RunError> {
+        let stalled = self.mem.stall_cycles();
+        if stalled != 0 {
+            self.cycles += stalled as u64;
+            return Ok(RunExit::Stalled(stalled));
         }
 
-        // The register state is rolled back on an instruction fault
+        // Without any IRQ sources, there is nothing to sample
+        self.advance(None, irq != 0)
+    }
+
+    /// Runs the instruction or enters the interrupt, sampling the lines
+    /// afterwards for `InterruptTiming::Hardware` if asked to.
+    #[inline]
+    fn advance(&mut self, interrupt: Option<Interrupt>, sample: bool) -> Result<RunExit, RunError> {
+        // The state is rolled back on a fault in the instruction or
+        // the interrupt entry
         let registers = self.reg_file;
-        match self.step() {
+        let cycles = self.cycles;
+        let exec_state = self.exec_state;
+        let io_port = self.io_port;
+        // Taken by the NMI entry or BRK before the writes are committed
+        let nmi_pending = self.nmi_pending.load(Ordering::Acquire);
+        self.write_buffer.active = self.write_mode == WriteMode::Transactional;
+        let run = match interrupt {
+            Some(interrupt) => self.enter_interrupt(interrupt),
+            None => self.step(),
+        };
+        self.write_buffer.active = false;
+        match run.and_then(|o| self.commit_writes().map(|_| o)) {
             Err(e) => {
                 self.fault = Some(e);
                 self.reg_file = registers;
+                self.cycles = cycles;
+                self.exec_state = exec_state;
+                self.io_port = io_port;
+                self.nmi_pending.store(nmi_pending, Ordering::Release);
+                self.write_buffer.clear();
                 Err(e)
             }
-            Ok(o) => Ok(o),
+            Ok(o) => {
+                if sample && self.interrupt_timing == InterruptTiming::Hardware {
+                    // CLI, SEI and PLP change the I flag after the lines are sampled.
+                    // The first instruction of the handler always runs.
+                    self.polled_interrupt = match o {
+                        RunExit::Executed(Insn::CLI | Insn::SEI | Insn::PLP) => self
+                            .poll_interrupts(
+                                registers.reg(Register::P) & Status::InterruptDisable.mask() != 0,
+                            ),
+                        RunExit::Executed(_) | RunExit::Trapped(_) => {
+                            self.poll_interrupts(self.flag_set(Status::InterruptDisable))
+                        }
+                        _ => None,
+                    };
+                }
+                Ok(o)
+            }
         }
     }
 }
 
+impl<M, H> Mos6502<M, H>
+where
+    M: SnapshotMemory,
+    H: Host<M>,
+{
+    /// Size of the machine snapshot: the processor state and the memory contents
+    pub fn machine_snapshot_len(&self) -> usize {
+        SNAPSHOT_SIZE + 4 + self.mem.contents_len()
+    }
+
+    /// Saves the processor state and the memory contents, returns
+    /// the number of the bytes written.
+    pub fn save_machine(&self, out: &mut [u8]) -> Result<usize, SnapshotError> {
+        let len = self.machine_snapshot_len();
+        let out = out.get_mut(..len).ok_or(SnapshotError::BufferTooSmall)?;
+        let (cpu, rest) = out.split_at_mut(SNAPSHOT_SIZE);
+        let (contents_len, contents) = rest.split_at_mut(4);
+
+        cpu.copy_from_slice(self.snapshot().as_bytes());
+        contents_len.copy_from_slice(&(contents.len() as u32).to_le_bytes());
+        self.mem.save_contents(contents);
+
+        Ok(len)
+    }
+
+    /// Restores the processor state and the memory contents saved by
+    /// `save_machine`. The processor state is restored only after
+    /// the memory has accepted the contents.
+    pub fn restore_machine(&mut self, bytes: &[u8]) -> Result<(), SnapshotError> {
+        let snapshot = Snapshot::from_bytes(bytes)?;
+        let mut contents_len = [0; 4];
+        contents_len.copy_from_slice(
+            bytes
+                .get(SNAPSHOT_SIZE..SNAPSHOT_SIZE + 4)
+                .ok_or(SnapshotError::Truncated)?,
+        );
+        let contents_len = u32::from_le_bytes(contents_len) as usize;
+        let contents = bytes
+            .get(SNAPSHOT_SIZE + 4..SNAPSHOT_SIZE + 4 + contents_len)
+            .ok_or(SnapshotError::Truncated)?;
+
+        let state = SavedState::read(&snapshot)?;
+        self.mem.restore_contents(contents)?;
+        self.apply_state(state);
+
+        Ok(())
+    }
+}
+
 #[cfg(feature = "std")]
 extern crate std;
 
@@ -894,3 +3082,9 @@ impl std::error::Error for MemoryError {}
 
 #[cfg(feature = "std")]
 impl std::error::Error for RunError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PinError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SnapshotError {}